@@ -0,0 +1,6 @@
+pub mod track;
+pub mod camera_track;
+
+#[allow(unused_imports)]
+pub use track::{Track, Interpolation, Keyframe};
+pub use camera_track::CameraTrack;
@@ -0,0 +1,63 @@
+use crate::maths::{vectors::V3, quaternion::Quaternion};
+use crate::rendering::Camera;
+
+use super::Track;
+
+// Camera movement over time, anything without keyframes stays as it is on the base camera
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct CameraTrack {
+    pub position: Track<V3>,
+    pub orientation: Track<Quaternion>,
+    pub fov: Track<f64>,
+}
+
+#[allow(dead_code)]
+impl CameraTrack {
+    pub fn new() -> Self {
+        Self { position: Track::new(super::Interpolation::Spline), orientation: Track::new(super::Interpolation::Spline), fov: Track::new(super::Interpolation::Linear) }
+    }
+
+    pub fn with_position(mut self, position: Track<V3>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_orientation(mut self, orientation: Track<Quaternion>) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn with_fov(mut self, fov: Track<f64>) -> Self {
+        self.fov = fov;
+        self
+    }
+
+    // Keyframes the camera exactly as it is at 'time'
+    pub fn add_keyframe(&mut self, time: f64, camera: &Camera) {
+        self.position.add_keyframe(time, camera.position);
+        self.orientation.add_keyframe(time, camera.orientation);
+        self.fov.add_keyframe(time, camera.fov);
+    }
+
+    // Camera at 'time', moving over the base camera's shutter interval if it has one
+    pub fn get_camera(&self, base: &Camera, time: f64) -> Camera {
+        let mut camera = *base;
+        (camera.position, camera.orientation) = self.get_pose(base, time + base.shutter.0);
+        if let Some(fov) = self.fov.get(time) { camera.fov = fov; }
+
+        camera.motion = None;
+        if base.shutter.1 > base.shutter.0 {
+            let (position, orientation) = self.get_pose(base, time + base.shutter.1);
+            camera = camera.with_motion(&position, orientation);
+        }
+        camera
+    }
+
+    fn get_pose(&self, base: &Camera, time: f64) -> (V3, Quaternion) {
+        (
+            self.position.get(time).unwrap_or(base.position),
+            self.orientation.get(time).unwrap_or(base.orientation).normalised(),
+        )
+    }
+}
@@ -0,0 +1,200 @@
+use crate::maths::{vectors::V3, quaternion::Quaternion};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step, // Hold each keyframe until the next one
+    Linear,
+    Spline, // Catmull-Rom, passing through every keyframe
+}
+
+// Values that can be animated by a track
+pub trait Keyframe: Copy {
+    fn lerp(&self, other: &Self, t: f64) -> Self;
+
+    // Cubic Hermite curve from 'self' to 'other', tangents are per segment rather than per second
+    fn hermite(&self, other: &Self, tangent_start: &Self, tangent_end: &Self, t: f64) -> Self;
+
+    // ('next' - 'previous') * 'scale'
+    fn get_tangent(previous: &Self, next: &Self, scale: f64) -> Self;
+
+    // Equivalent value closest to 'reference', for types with more than one representation of the same value
+    fn aligned_to(&self, _reference: &Self) -> Self { *self }
+}
+
+impl Keyframe for f64 {
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+
+    fn hermite(&self, other: &Self, tangent_start: &Self, tangent_end: &Self, t: f64) -> Self {
+        let (h00, h10, h01, h11) = get_hermite_basis(t);
+        h00 * self + h10 * tangent_start + h01 * other + h11 * tangent_end
+    }
+
+    fn get_tangent(previous: &Self, next: &Self, scale: f64) -> Self {
+        (next - previous) * scale
+    }
+}
+
+impl Keyframe for V3 {
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        self + ((other - self) * t)
+    }
+
+    fn hermite(&self, other: &Self, tangent_start: &Self, tangent_end: &Self, t: f64) -> Self {
+        let (h00, h10, h01, h11) = get_hermite_basis(t);
+        (self * h00) + (tangent_start * h10) + (other * h01) + (tangent_end * h11)
+    }
+
+    fn get_tangent(previous: &Self, next: &Self, scale: f64) -> Self {
+        (next - previous) * scale
+    }
+}
+
+impl Keyframe for Quaternion {
+    // Spherical, so the rotation turns at a constant rate
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        let other = other.aligned_to(self);
+        let dot = self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z;
+        if dot > 0.9995 {
+            // Nearly identical, where slerp would divide by almost zero
+            return Quaternion::new(self.w.lerp(&other.w, t), self.x.lerp(&other.x, t), self.y.lerp(&other.y, t), self.z.lerp(&other.z, t)).normalised();
+        }
+
+        let theta = dot.clamp(-1.0, 1.0).acos();
+        let (a, b) = (((1.0 - t) * theta).sin() / theta.sin(), (t * theta).sin() / theta.sin());
+        Quaternion::new(a * self.w + b * other.w, a * self.x + b * other.x, a * self.y + b * other.y, a * self.z + b * other.z).normalised()
+    }
+
+    fn hermite(&self, other: &Self, tangent_start: &Self, tangent_end: &Self, t: f64) -> Self {
+        let (h00, h10, h01, h11) = get_hermite_basis(t);
+        let get = |f: fn(&Quaternion) -> f64| h00 * f(self) + h10 * f(tangent_start) + h01 * f(other) + h11 * f(tangent_end);
+        Quaternion::new(get(|q| q.w), get(|q| q.x), get(|q| q.y), get(|q| q.z)).normalised()
+    }
+
+    fn get_tangent(previous: &Self, next: &Self, scale: f64) -> Self {
+        Quaternion::new((next.w - previous.w) * scale, (next.x - previous.x) * scale, (next.y - previous.y) * scale, (next.z - previous.z) * scale)
+    }
+
+    // q and -q are the same rotation, but interpolating between them goes the long way round
+    fn aligned_to(&self, reference: &Self) -> Self {
+        let dot = self.w * reference.w + self.x * reference.x + self.y * reference.y + self.z * reference.z;
+        if dot < 0.0 { Quaternion::new(-self.w, -self.x, -self.y, -self.z) } else { *self }
+    }
+}
+
+fn get_hermite_basis(t: f64) -> (f64, f64, f64, f64) {
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0, t3 - 2.0 * t2 + t, -2.0 * t3 + 3.0 * t2, t3 - t2)
+}
+
+// Keyframed value over time, held at the first and last keyframes outside their range
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Track<T: Keyframe> {
+    keyframes: Vec<(f64, T)>, // Sorted by time
+    pub interpolation: Interpolation,
+}
+
+#[allow(dead_code)]
+impl<T: Keyframe> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self { keyframes: Vec::new(), interpolation }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(Interpolation::Step).with_keyframe(0.0, value)
+    }
+
+    pub fn with_keyframe(mut self, time: f64, value: T) -> Self {
+        self.add_keyframe(time, value);
+        self
+    }
+
+    // Replaces any keyframe already at 'time'
+    pub fn add_keyframe(&mut self, time: f64, value: T) {
+        match self.keyframes.binary_search_by(|k| k.0.partial_cmp(&time).unwrap()) {
+            Ok(i) => self.keyframes[i] = (time, value),
+            Err(i) => self.keyframes.insert(i, (time, value)),
+        }
+    }
+
+    pub fn get_keyframes(&self) -> &[(f64, T)] {
+        &self.keyframes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    // (first, last) keyframe times
+    pub fn get_range(&self) -> Option<(f64, f64)> {
+        Some((self.keyframes.first()?.0, self.keyframes.last()?.0))
+    }
+
+    pub fn get(&self, time: f64) -> Option<T> {
+        let keys = &self.keyframes;
+        if keys.is_empty() { return None; }
+
+        // Index of the first keyframe after 'time'
+        let next = keys.partition_point(|k| k.0 <= time);
+        if next == 0 { return Some(keys[0].1); }
+        if next == keys.len() { return Some(keys[keys.len() - 1].1); }
+
+        let (i1, i2) = (next - 1, next);
+        let (t1, p1) = keys[i1];
+        let (t2, p2) = keys[i2];
+        let t = (time - t1) / (t2 - t1);
+
+        match self.interpolation {
+            Interpolation::Step => Some(p1),
+            Interpolation::Linear => Some(p1.lerp(&p2, t)),
+            Interpolation::Spline => {
+                let (t0, p0) = keys[i1.saturating_sub(1)];
+                let (t3, p3) = keys[(i2 + 1).min(keys.len() - 1)];
+
+                let p0 = p0.aligned_to(&p1);
+                let p2 = p2.aligned_to(&p1);
+                let p3 = p3.aligned_to(&p2);
+
+                // Tangents scaled by the segment's share of the surrounding time so uneven keyframe spacing doesn't overshoot
+                let get_scale = |span: f64| if span > 0.0 { (t2 - t1) / span } else { 0.0 };
+                let m1 = T::get_tangent(&p0, &p2, get_scale(t2 - t0));
+                let m2 = T::get_tangent(&p1, &p3, get_scale(t3 - t1));
+
+                Some(p1.hermite(&p2, &m1, &m2, t))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::maths::{vectors::V3, quaternion::Quaternion};
+
+    use super::{Track, Interpolation};
+
+    #[test]
+    fn tracks_interpolate_between_keyframes() {
+        let linear = Track::new(Interpolation::Linear).with_keyframe(2.0, 10.0).with_keyframe(0.0, 0.0);
+        assert_eq!(linear.get(-1.0), Some(0.0));
+        assert_eq!(linear.get(0.5), Some(2.5));
+        assert_eq!(linear.get(5.0), Some(10.0));
+
+        let step = Track::new(Interpolation::Step).with_keyframe(0.0, 1.0).with_keyframe(1.0, 2.0);
+        assert_eq!(step.get(0.99), Some(1.0));
+
+        let spline = Track::new(Interpolation::Spline).with_keyframe(0.0, V3::ZERO).with_keyframe(1.0, V3::UP).with_keyframe(3.0, V3::ZERO);
+        assert!((spline.get(1.0).unwrap() - V3::UP).magnitude() < 1e-9);
+        assert!(spline.get(0.9).unwrap().y > 0.9);
+
+        let rotation = Track::new(Interpolation::Linear)
+            .with_keyframe(0.0, Quaternion::IDENTITY)
+            .with_keyframe(1.0, Quaternion::from_axis_angle(&V3::UP, PI / 2.0));
+        let halfway = rotation.get(0.5).unwrap().get_forward();
+        assert!((halfway.angle_to(&V3::FORWARD) - PI / 4.0).abs() < 1e-9);
+    }
+}
@@ -24,6 +24,14 @@ impl Colour {
         ((self.r * 255.0) as u8, (self.g * 255.0) as u8, (self.b * 255.0) as u8)
     }
 
+    pub fn as_f64(&self) -> (f64, f64, f64) {
+        (self.r, self.g, self.b)
+    }
+
+    pub fn distance(&self, rhs: &Colour) -> f64 {
+        ((self.r - rhs.r).powi(2) + (self.g - rhs.g).powi(2) + (self.b - rhs.b).powi(2)).sqrt()
+    }
+
     pub fn ceiling_limited(&self) -> Colour {
         Colour { r: self.r.clamp(0.0, 1.0), g: self.g.clamp(0.0, 1.0), b: self.b.clamp(0.0, 1.0) }
     }
@@ -38,6 +46,18 @@ impl Colour {
         self * (1.0 / max)
     }
 
+    pub fn max_component(&self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.r <= 0.0 && self.g <= 0.0 && self.b <= 0.0
+    }
+
     pub const BLACK: Colour = Colour { r: 0.0, g: 0.0, b: 0.0 };
     pub const RED: Colour = Colour { r: 1.0, g: 0.0, b: 0.0 };
     pub const GREEN: Colour = Colour { r: 0.0, g: 1.0, b: 0.0 };
@@ -0,0 +1,67 @@
+pub mod equirectangular_map;
+#[allow(unused_imports)]
+pub use equirectangular_map::EquirectangularMap;
+pub mod cube_map;
+#[allow(unused_imports)]
+pub use cube_map::CubeMap;
+pub mod preetham_sky;
+pub use preetham_sky::PreethamSky;
+
+use std::f64::consts::PI;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{colour::Colour, maths::vectors::V3};
+
+pub trait Environment {
+    fn get_colour(&self, direction: &V3) -> Colour;
+
+    // Returns (normalised direction, pdf per unit solid angle)
+    fn sample(&self, rng: &mut dyn Sampler) -> (V3, f64) {
+        (V3::get_random(&V3::UP, PI, rng), 1.0 / (4.0 * PI))
+    }
+
+    fn get_pdf(&self, _direction: &V3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// Linear, unclamped image data for HDR (or LDR) environment maps
+#[allow(dead_code)]
+pub struct EnvironmentImage {
+    pub size: (usize, usize),
+    data: Vec<Colour>
+}
+
+#[allow(dead_code)]
+impl EnvironmentImage {
+    pub fn new(image_path: &str) -> Result<Self, String> {
+        println!("Loading environment [{}]", image_path);
+
+        let img = image::open(image_path);
+
+        if img.is_err() {
+            return Err(format!("Loading environment failed [{}]", image_path));
+        }
+
+        let img = img.unwrap().into_rgb32f();
+
+        let size = (img.dimensions().0 as usize, img.dimensions().1 as usize);
+        let data = img.pixels().map(|p| Colour::from_f64(p.0[0] as f64, p.0[1] as f64, p.0[2] as f64)).collect();
+
+        Ok(Self { size, data })
+    }
+
+    // 'data' is row-major
+    pub fn from_data(size: (usize, usize), data: Vec<Colour>) -> Self {
+        Self { size, data }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> &Colour {
+        &self.data[y.min(self.size.1 - 1) * self.size.0 + x.min(self.size.0 - 1)]
+    }
+
+    pub fn get_uv(&self, position: (f64, f64)) -> &Colour {
+        self.get((position.0 * self.size.0 as f64) as usize, (position.1 * self.size.1 as f64) as usize)
+    }
+}
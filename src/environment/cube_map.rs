@@ -0,0 +1,177 @@
+use crate::maths::sampler::Sampler;
+
+use crate::{colour::Colour, maths::{vectors::V3, distribution::Distribution1D}};
+
+use super::{Environment, EnvironmentImage};
+
+// Six square faces in the order +X, -X, +Y, -Y, +Z, -Z
+#[allow(dead_code)]
+pub struct CubeMap {
+    faces: Vec<EnvironmentImage>,
+    face_size: usize,
+    distribution: Distribution1D,
+}
+
+#[allow(dead_code)]
+impl CubeMap {
+    pub fn new(image_paths: [&str; 6]) -> Result<Self, String> {
+        let mut faces = Vec::with_capacity(6);
+        for path in image_paths {
+            faces.push(EnvironmentImage::new(path)?);
+        }
+
+        Self::from_faces(faces)
+    }
+
+    pub fn from_faces(faces: Vec<EnvironmentImage>) -> Result<Self, String> {
+        if faces.len() != 6 { return Err("Cube maps need six faces".to_string()); }
+
+        let face_size = faces[0].size.0;
+        if faces.iter().any(|f| f.size != (face_size, face_size)) {
+            return Err("Cube map faces must be square and the same size".to_string());
+        }
+
+        // Weight by brightness and by the solid angle each texel covers
+        let mut weights = Vec::with_capacity(6 * face_size * face_size);
+        for face in &faces {
+            for y in 0..face_size {
+                for x in 0..face_size {
+                    let a = (2.0 * (x as f64 + 0.5) / face_size as f64) - 1.0;
+                    let b = (2.0 * (y as f64 + 0.5) / face_size as f64) - 1.0;
+                    weights.push(face.get(x, y).luminance() * Self::get_solid_angle_scale(a, b));
+                }
+            }
+        }
+
+        Ok(Self { faces, face_size, distribution: Distribution1D::new(&weights) })
+    }
+
+    // Solid angle per unit area on the face at (a, b)
+    fn get_solid_angle_scale(a: f64, b: f64) -> f64 {
+        1.0 / (1.0 + a * a + b * b).powf(1.5)
+    }
+
+    // Returns (face, a, b) with a and b in [-1, 1]
+    fn get_face(direction: &V3) -> (usize, f64, f64) {
+        let (ax, ay, az) = (direction.x.abs(), direction.y.abs(), direction.z.abs());
+
+        if ax >= ay && ax >= az {
+            if direction.x > 0.0 { (0, -direction.z / ax, -direction.y / ax) }
+            else { (1, direction.z / ax, -direction.y / ax) }
+        }
+        else if ay >= az {
+            if direction.y > 0.0 { (2, direction.x / ay, direction.z / ay) }
+            else { (3, direction.x / ay, -direction.z / ay) }
+        }
+        else if direction.z > 0.0 { (4, direction.x / az, -direction.y / az) }
+        else { (5, -direction.x / az, -direction.y / az) }
+    }
+
+    fn get_direction(face: usize, a: f64, b: f64) -> V3 {
+        match face {
+            0 => V3::new(1.0, -b, -a),
+            1 => V3::new(-1.0, -b, a),
+            2 => V3::new(a, 1.0, b),
+            3 => V3::new(a, -1.0, -b),
+            4 => V3::new(a, -b, 1.0),
+            _ => V3::new(-a, -b, -1.0),
+        }
+    }
+
+    fn get_texel(&self, a: f64, b: f64) -> (usize, usize) {
+        let size = self.face_size as f64;
+        ((((a + 1.0) * 0.5 * size) as usize).min(self.face_size - 1), (((b + 1.0) * 0.5 * size) as usize).min(self.face_size - 1))
+    }
+
+    fn get_texel_area(&self) -> f64 {
+        (2.0 / self.face_size as f64).powi(2)
+    }
+}
+
+impl Environment for CubeMap {
+    fn get_colour(&self, direction: &V3) -> Colour {
+        let (face, a, b) = Self::get_face(direction);
+        let (x, y) = self.get_texel(a, b);
+        *self.faces[face].get(x, y)
+    }
+
+    fn sample(&self, rng: &mut dyn Sampler) -> (V3, f64) {
+        let (index, probability, _) = self.distribution.sample(rng.get_1d());
+
+        let face = index / (self.face_size * self.face_size);
+        let texel = index % (self.face_size * self.face_size);
+        let (u, v) = rng.get_2d();
+        let a = (2.0 * ((texel % self.face_size) as f64 + u) / self.face_size as f64) - 1.0;
+        let b = (2.0 * ((texel / self.face_size) as f64 + v) / self.face_size as f64) - 1.0;
+
+        (Self::get_direction(face, a, b).normalised(), probability / (self.get_texel_area() * Self::get_solid_angle_scale(a, b)))
+    }
+
+    fn get_pdf(&self, direction: &V3) -> f64 {
+        let (face, a, b) = Self::get_face(direction);
+        let (x, y) = self.get_texel(a, b);
+        let index = (face * self.face_size * self.face_size) + (y * self.face_size) + x;
+
+        self.distribution.get_probability(index) / (self.get_texel_area() * Self::get_solid_angle_scale(a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::colour::Colour;
+    use crate::maths::{vectors::V3, sampler::{Sampler, IndependentSampler}};
+
+    use super::{Environment, EnvironmentImage, CubeMap};
+
+    // Dim everywhere but one bright texel on the +Y face
+    fn get_map() -> CubeMap {
+        let faces = (0..6).map(|face| {
+            let data = (0..16).map(|i| if face == 2 && i == 5 { Colour::from_f64(50.0, 50.0, 50.0) } else { Colour::from_f64(0.1, 0.2, 0.3) }).collect();
+            EnvironmentImage::from_data((4, 4), data)
+        }).collect();
+        CubeMap::from_faces(faces).unwrap()
+    }
+
+    #[test]
+    fn faces_and_directions_round_trip() {
+        for face in 0..6 {
+            for (a, b) in [(0.0, 0.0), (0.5, -0.25), (-0.9, 0.9)] {
+                assert_eq!(CubeMap::get_face(&CubeMap::get_direction(face, a, b)), (face, a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn samples_match_their_pdf() {
+        let map = get_map();
+        let mut rng = IndependentSampler::new(0);
+        let count = 20000;
+        let mut total = 0.0;
+
+        for i in 0..count {
+            rng.start_sample((0, 0), i);
+            let (direction, pdf) = map.sample(&mut rng);
+            assert!((pdf - map.get_pdf(&direction)).abs() < 1e-6 * pdf, "{:?}: {} vs {}", direction, pdf, map.get_pdf(&direction));
+            total += 1.0 / pdf;
+        }
+
+        // Every direction can be chosen, so 1 / pdf averages to the area of the sphere
+        assert!((total / count as f64 - 4.0 * PI).abs() < 0.05 * 4.0 * PI, "{}", total / count as f64);
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = get_map();
+        let mut rng = IndependentSampler::new(1);
+        let count = 20000;
+
+        let total: f64 = (0..count).map(|i| {
+            rng.start_sample((0, 0), i);
+            map.get_pdf(&V3::get_random(&V3::UP, PI, &mut rng))
+        }).sum();
+
+        assert!((4.0 * PI * total / count as f64 - 1.0).abs() < 0.05, "{}", 4.0 * PI * total / count as f64);
+    }
+}
@@ -0,0 +1,118 @@
+use std::f64::consts::PI;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{colour::Colour, maths::{vectors::V3, distribution::Distribution2D}};
+
+use super::{Environment, EnvironmentImage};
+
+// Latitude-longitude map - v = 0 is straight up, u = 0.5 is along FORWARD
+#[allow(dead_code)]
+pub struct EquirectangularMap {
+    image: EnvironmentImage,
+    distribution: Distribution2D,
+}
+
+#[allow(dead_code)]
+impl EquirectangularMap {
+    pub fn new(image_path: &str) -> Result<Self, String> {
+        Ok(Self::from_image(EnvironmentImage::new(image_path)?))
+    }
+
+    pub fn from_image(image: EnvironmentImage) -> Self {
+        // Weight by brightness and by the solid angle each row covers
+        let mut weights = Vec::with_capacity(image.size.0 * image.size.1);
+        for y in 0..image.size.1 {
+            let sin_theta = (PI * (y as f64 + 0.5) / image.size.1 as f64).sin();
+            for x in 0..image.size.0 {
+                weights.push(image.get(x, y).luminance() * sin_theta);
+            }
+        }
+
+        let distribution = Distribution2D::new(&weights, image.size.0);
+
+        Self { image, distribution }
+    }
+
+    fn direction_to_uv(direction: &V3) -> (f64, f64) {
+        let direction = direction.normalised();
+        ((direction.z.atan2(direction.x) / (2.0 * PI)) + 0.5, direction.y.clamp(-1.0, 1.0).acos() / PI)
+    }
+
+    fn uv_to_direction(position: (f64, f64)) -> V3 {
+        let theta = position.1 * PI;
+        let phi = (position.0 - 0.5) * 2.0 * PI;
+        V3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+    }
+}
+
+impl Environment for EquirectangularMap {
+    fn get_colour(&self, direction: &V3) -> Colour {
+        *self.image.get_uv(Self::direction_to_uv(direction))
+    }
+
+    fn sample(&self, rng: &mut dyn Sampler) -> (V3, f64) {
+        let (position, pdf) = self.distribution.sample(rng.get_2d());
+        let sin_theta = (position.1 * PI).sin();
+        if sin_theta <= 0.0 { return (V3::UP, 0.0); }
+
+        (Self::uv_to_direction(position), pdf / (2.0 * PI * PI * sin_theta))
+    }
+
+    fn get_pdf(&self, direction: &V3) -> f64 {
+        let position = Self::direction_to_uv(direction);
+        let sin_theta = (position.1 * PI).sin();
+        if sin_theta <= 0.0 { return 0.0; }
+
+        self.distribution.get_pdf(position) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::colour::Colour;
+    use crate::maths::{vectors::V3, sampler::{Sampler, IndependentSampler}};
+
+    use super::{Environment, EnvironmentImage, EquirectangularMap};
+
+    // Dim everywhere but one bright texel, so sampling is far from uniform
+    fn get_map() -> EquirectangularMap {
+        let size = (16, 8);
+        let data = (0..size.0 * size.1).map(|i| if i == 37 { Colour::from_f64(50.0, 50.0, 50.0) } else { Colour::from_f64(0.1, 0.2, 0.3) }).collect();
+        EquirectangularMap::from_image(EnvironmentImage::from_data(size, data))
+    }
+
+    #[test]
+    fn samples_match_their_pdf() {
+        let map = get_map();
+        let mut rng = IndependentSampler::new(0);
+        let count = 20000;
+        let mut total = 0.0;
+
+        for i in 0..count {
+            rng.start_sample((0, 0), i);
+            let (direction, pdf) = map.sample(&mut rng);
+            assert!((pdf - map.get_pdf(&direction)).abs() < 1e-6 * pdf, "{:?}: {} vs {}", direction, pdf, map.get_pdf(&direction));
+            total += 1.0 / pdf;
+        }
+
+        // Every direction can be chosen, so 1 / pdf averages to the area of the sphere
+        assert!((total / count as f64 - 4.0 * PI).abs() < 0.05 * 4.0 * PI, "{}", total / count as f64);
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = get_map();
+        let mut rng = IndependentSampler::new(1);
+        let count = 20000;
+
+        let total: f64 = (0..count).map(|i| {
+            rng.start_sample((0, 0), i);
+            map.get_pdf(&V3::get_random(&V3::UP, PI, &mut rng))
+        }).sum();
+
+        assert!((4.0 * PI * total / count as f64 - 1.0).abs() < 0.05, "{}", 4.0 * PI * total / count as f64);
+    }
+}
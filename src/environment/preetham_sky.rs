@@ -0,0 +1,205 @@
+use std::f64::consts::PI;
+
+use crate::{colour::Colour, maths::vectors::V3, lights::DirectionalLight};
+
+use super::Environment;
+
+const SUN_ANGULAR_RADIUS: f64 = 0.01;
+const SUN_DISC_BRIGHTNESS: f64 = 20.0;
+
+// Analytic daylight model from Preetham et al. 'A Practical Analytic Model for Daylight'
+#[allow(dead_code)]
+pub struct PreethamSky {
+    sun_direction: V3, // Towards the sun
+    turbidity: f64,
+    scale: f64, // Converts from kcd/m^2
+    perez_luminance: [f64; 5],
+    perez_x: [f64; 5],
+    perez_y: [f64; 5],
+    zenith: (f64, f64, f64), // Y, x, y
+    sun_disc: bool,
+}
+
+#[allow(dead_code)]
+impl PreethamSky {
+    pub fn new(sun_direction: &V3, turbidity: f64) -> Self {
+        let sun_direction = sun_direction.normalised();
+        let t = turbidity;
+        let theta_s = sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let perez_luminance = [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703];
+        let perez_x = [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452];
+        let perez_y = [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529];
+
+        let chi = ((4.0 / 9.0) - (t / 120.0)) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let zenith_x = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_y = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        Self {
+            sun_direction,
+            turbidity,
+            scale: 0.05,
+            perez_luminance,
+            perez_x,
+            perez_y,
+            zenith: (zenith_luminance, zenith_x, zenith_y),
+            sun_disc: true,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    // Leaves the sun out of the sky, for scenes lighting it with 'get_sun' instead so it isn't counted twice
+    pub fn with_sun_disc(mut self, sun_disc: bool) -> Self {
+        self.sun_disc = sun_disc;
+        self
+    }
+
+    fn perez(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+        (1.0 + coefficients[0] * (coefficients[1] / cos_theta).exp()) *
+            (1.0 + coefficients[2] * (coefficients[3] * gamma).exp() + coefficients[4] * gamma.cos().powi(2))
+    }
+
+    fn get_sky_colour(&self, direction: &V3) -> Colour {
+        // Below the horizon continues the horizon colour
+        let cos_theta = direction.y.max(0.001);
+        let gamma = direction.angle_to(&self.sun_direction);
+        let theta_s = self.sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let relative = |coefficients: &[f64; 5], zenith: f64| {
+            zenith * Self::perez(coefficients, cos_theta, gamma) / Self::perez(coefficients, 1.0, theta_s)
+        };
+
+        let luminance = relative(&self.perez_luminance, self.zenith.0) * self.scale;
+        let x = relative(&self.perez_x, self.zenith.1);
+        let y = relative(&self.perez_y, self.zenith.2);
+
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    // Colour of direct sunlight after passing through the atmosphere
+    pub fn get_sun_colour(&self) -> Colour {
+        if self.sun_direction.y <= 0.0 { return Colour::BLACK; }
+
+        let theta_s = self.sun_direction.y.acos();
+        let relative_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        // Rayleigh and aerosol transmittance at representative red, green and blue wavelengths (um)
+        let transmittance = |wavelength: f64| {
+            (-0.008735 * wavelength.powf(-4.08) * relative_mass).exp() * (-beta * wavelength.powf(-1.3) * relative_mass).exp()
+        };
+
+        Colour::from_f64(transmittance(0.680), transmittance(0.550), transmittance(0.440))
+    }
+
+    pub fn get_sun(&self, intensity: f64) -> DirectionalLight {
+        DirectionalLight::new(&(self.sun_direction * -1.0), &self.get_sun_colour(), intensity)
+    }
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Colour {
+    if y <= 0.0 { return Colour::BLACK; }
+
+    let cx = x * luminance / y;
+    let cz = (1.0 - x - y) * luminance / y;
+
+    Colour::from_f64(
+        (3.2406 * cx - 1.5372 * luminance - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * luminance + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * luminance + 1.0570 * cz).max(0.0),
+    )
+}
+
+impl Environment for PreethamSky {
+    fn get_colour(&self, direction: &V3) -> Colour {
+        let direction = direction.normalised();
+
+        if self.sun_disc && direction.y > 0.0 && direction.angle_to(&self.sun_direction) < SUN_ANGULAR_RADIUS {
+            return self.get_sun_colour() * SUN_DISC_BRIGHTNESS;
+        }
+
+        self.get_sky_colour(&direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::maths::{vectors::V3, sampler::{Sampler, IndependentSampler}};
+
+    use super::{Environment, PreethamSky, SUN_DISC_BRIGHTNESS};
+
+    #[test]
+    fn zenith_has_the_zenith_luminance() {
+        let sky = PreethamSky::new(&V3::new(1.0, 1.0, 0.3), 3.0);
+        let luminance = sky.get_colour(&V3::UP).luminance();
+
+        // Only the xyY to RGB conversion separates them
+        assert!((luminance - sky.zenith.0 * sky.scale).abs() < 0.01 * luminance, "{} vs {}", luminance, sky.zenith.0 * sky.scale);
+    }
+
+    #[test]
+    fn sky_is_brightest_around_the_sun() {
+        let sky = PreethamSky::new(&V3::new(1.0, 0.5, 0.0), 3.0);
+        let near_sun = sky.get_colour(&V3::new(1.0, 0.6, 0.1)).luminance();
+        let away_from_sun = sky.get_colour(&V3::new(-1.0, 0.6, 0.1)).luminance();
+
+        assert!(near_sun > 2.0 * away_from_sun, "{} vs {}", near_sun, away_from_sun);
+    }
+
+    #[test]
+    fn sun_disc_can_be_left_out() {
+        let (sun_direction, direction) = (V3::new(1.0, 1.0, 0.0), V3::new(1.0, 1.005, 0.0).normalised());
+        let sky = PreethamSky::new(&sun_direction, 3.0);
+        let with_disc = sky.get_colour(&direction);
+        let without_disc = PreethamSky::new(&sun_direction, 3.0).with_sun_disc(false).get_colour(&direction);
+
+        // Just inside the disc
+        assert!(with_disc.distance(&(sky.get_sun_colour() * SUN_DISC_BRIGHTNESS)) < 1e-9, "{:?}", with_disc);
+        assert!(without_disc.distance(&sky.get_sky_colour(&direction)) < 1e-9, "{:?}", without_disc);
+    }
+
+    #[test]
+    fn sun_reddens_towards_the_horizon() {
+        let blueness = |elevation: f64| {
+            let (r, _, b) = PreethamSky::new(&V3::new(elevation.cos(), elevation.sin(), 0.0), 3.0).get_sun_colour().as_f64();
+            b / r
+        };
+
+        assert!(blueness(0.1) < blueness(0.5) && blueness(0.5) < blueness(1.2), "{} {} {}", blueness(0.1), blueness(0.5), blueness(1.2));
+        assert!(PreethamSky::new(&V3::new(1.0, -0.1, 0.0), 3.0).get_sun_colour().is_black());
+    }
+
+    #[test]
+    fn samples_are_uniform_like_their_pdf() {
+        let sky = PreethamSky::new(&V3::new(1.0, 1.0, 0.3), 3.0);
+        let mut rng = IndependentSampler::new(0);
+        let count = 20000;
+        let (mut total_y, mut total_y_squared) = (0.0, 0.0);
+
+        for i in 0..count {
+            rng.start_sample((0, 0), i);
+            let (direction, pdf) = sky.sample(&mut rng);
+            assert!((pdf - sky.get_pdf(&direction)).abs() < 1e-9 && (pdf - 1.0 / (4.0 * PI)).abs() < 1e-9);
+            assert!((direction.magnitude() - 1.0).abs() < 1e-9);
+            total_y += direction.y;
+            total_y_squared += direction.y * direction.y;
+        }
+
+        // Moments of a uniformly distributed unit vector
+        assert!((total_y / count as f64).abs() < 0.02, "{}", total_y / count as f64);
+        assert!((total_y_squared / count as f64 - 1.0 / 3.0).abs() < 0.02, "{}", total_y_squared / count as f64);
+    }
+}
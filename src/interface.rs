@@ -1,28 +1,45 @@
-use std::f64::consts::PI;
+use std::sync::Arc;
 use std::time::Instant;
 
-use crate::maths::vectors::V3;
-use crate::rendering::{RenderConfig, Camera, self};
-use rand::rngs::ThreadRng;
-use rand::thread_rng;
+use crate::maths::{vectors::V3, quaternion::Quaternion, transform::Transform};
+use crate::rendering::{RenderConfig, Camera, ScreenshotQueue, ScreenshotJob, JobStatus, self};
+use crate::scene::{SceneNode, scene_file};
 use sdl2::EventPump;
 use sdl2::render::Canvas;
 use sdl2::surface::Surface;
 use sdl2::video::Window;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 
 
 pub struct Interface 
 {
-    render_config: RenderConfig,
+    render_config: Arc<RenderConfig>,
     camera: Camera,
-    rng: ThreadRng
+    denoise: bool,
+    screenshots: ScreenshotQueue,
+    show_screenshot: bool, // Show the screenshot being rendered instead of the live view
+    scene: SceneNode,
+    selected_node: Option<usize>, // Depth first index into 'scene'
+    scene_changed: bool, // Edited since the render config was last rebuilt
+    scene_path: Option<String>, // File the scene was loaded from, reloaded with L
 }
 
 impl Interface {
-    pub fn start(render_config: RenderConfig, camera: Camera) {
-        let mut this = Self { render_config, camera, rng: thread_rng() };
+    pub fn start(mut render_config: RenderConfig, scene: SceneNode, scene_path: Option<String>, camera: Camera) {
+        render_config.set_scene(&scene);
+        let render_config = Arc::new(render_config);
+        let mut this = Self { 
+            denoise: render_config.denoise, 
+            screenshots: ScreenshotQueue::new(), 
+            show_screenshot: false, 
+            render_config, 
+            camera,
+            scene,
+            selected_node: None,
+            scene_changed: false,
+            scene_path,
+        };
 
         let (mut canvas, mut event_pump) = Self::initialise_display(&this);
 
@@ -35,9 +52,26 @@ impl Interface {
     
             let exit = Self::handle_input(&mut this, &mut event_pump, delta_time);
             if exit { break 'running; }
+            this.update_scene();
             
-            let mut pixel_data = this.camera.get_image(&this.render_config, &mut this.rng, false, false);
-            // let mut pixel_data = this.camera.get_image_threaded(&this.render_config, false);
+            let active_screenshot = this.screenshots.get_active();
+            Self::update_title(&mut canvas, &active_screenshot, this.screenshots.get_pending().len());
+
+            let mut pixel_data;
+            match (&active_screenshot, this.show_screenshot) {
+                (Some(job), true) => {
+                    pixel_data = rendering::get_scaled(&job.target.get_pixel_data(), job.target.resolution, this.render_config.resolution);
+                },
+                _ => {
+                    if this.denoise {
+                        let colours = this.camera.get_linear_image(&this.render_config, false, false);
+                        pixel_data = rendering::get_pixel_data(&rendering::denoise(&this.camera, &this.render_config, &colours, false));
+                    }
+                    else { pixel_data = this.camera.get_image(&this.render_config, false, false); }
+                    // let mut pixel_data = this.camera.get_image_threaded(&this.render_config, false);
+                    Self::draw_crosshair(&mut pixel_data, this.render_config.resolution);
+                }
+            }
     
             let surface = Surface::from_data(&mut pixel_data, 
                 this.render_config.resolution.0, 
@@ -49,6 +83,8 @@ impl Interface {
             canvas.copy(&surface.as_texture(&canvas.texture_creator()).unwrap(), None, None).unwrap();
             canvas.present();
         }
+
+        this.screenshots.shutdown();
     }
 
     fn initialise_display(&self) -> (Canvas<Window>, EventPump) {
@@ -71,13 +107,104 @@ impl Interface {
         (canvas, event_pump)
     }
 
+    // Rebuilds the render config from the scene after edits, waiting until no screenshot is using it
+    fn update_scene(&mut self) {
+        if !self.scene_changed { return; }
+        if !self.screenshots.get_pending().is_empty() { return; }
+
+        if let Some(render_config) = Arc::get_mut(&mut self.render_config) {
+            render_config.set_scene(&self.scene);
+            self.scene_changed = false;
+        }
+    }
+
+    // Picks up changes made to the scene file since it was loaded, replacing any edits made in the viewer
+    fn reload_scene(&mut self) {
+        let Some(path) = &self.scene_path else { println!("The scene wasn't loaded from a file"); return; };
+
+        match scene_file::load(path) {
+            Ok(scene) => {
+                self.scene = scene;
+                self.selected_node = None;
+                self.scene_changed = true;
+            },
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    fn select_next_node(&mut self) {
+        let next = self.selected_node.map_or(0, |i| i + 1);
+        self.selected_node = if next < self.scene.get_node_count() { Some(next) } else { None };
+
+        match self.selected_node.and_then(|i| self.scene.get_node_mut(i)) {
+            Some(node) => println!("Selected '{}'", node.name),
+            None => println!("Nothing selected"),
+        }
+    }
+
+    // Moves the selected node within its parent's space
+    fn edit_selected_node(&mut self, translation: &V3, rotation: f64) {
+        let Some(node) = self.selected_node.and_then(|i| self.scene.get_node_mut(i)) else { return; };
+
+        node.transform = Transform::translation(translation) * node.transform * Transform::rotation(&Quaternion::from_axis_angle(&V3::UP, rotation));
+        self.scene_changed = true;
+    }
+
+    fn update_title(canvas: &mut Canvas<Window>, active_screenshot: &Option<Arc<ScreenshotJob>>, pending: usize) {
+        let title = match active_screenshot {
+            None => "Raytracing".to_string(),
+            Some(job) => match job.get_status() {
+                JobStatus::Rendering(progress) => {
+                    let eta = progress.get_eta().map_or(String::new(), |eta| format!(", ETA {:.0?}", eta));
+                    format!("Raytracing - screenshot {} {:.0}%{} ({} pending)", job.id, progress.get_fraction() * 100.0, eta, pending)
+                },
+                _ => format!("Raytracing - screenshot {} starting ({} pending)", job.id, pending),
+            }
+        };
+
+        if canvas.window().title() != title { canvas.window_mut().set_title(&title).unwrap(); }
+    }
+
+    fn draw_crosshair(pixel_data: &mut [u8], resolution: (u32, u32)) {
+        let centre = ((resolution.0 / 2) as i32, (resolution.1 / 2) as i32);
+
+        for offset in -3..=3 {
+            for (x, y) in [(centre.0 + offset, centre.1), (centre.0, centre.1 + offset)] {
+                if x < 0 || y < 0 || x >= resolution.0 as i32 || y >= resolution.1 as i32 { continue; }
+                let i = ((y as u32 * resolution.0 + x as u32) * 3) as usize;
+                pixel_data[i] = 255 - pixel_data[i];
+                pixel_data[i + 1] = 255 - pixel_data[i + 1];
+                pixel_data[i + 2] = 255 - pixel_data[i + 2];
+            }
+        }
+    }
+
     fn handle_input(&mut self, event_pump: &mut EventPump, delta_time: f64) -> bool {
         for event in event_pump.poll_iter() {
         match event {
             Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::P), .. } => return true,
+            Event::KeyDown { keycode: Some(Keycode::V), repeat: false, .. } => {
+                self.camera.projection = self.camera.projection.next();
+                println!("Projection: {:?}", self.camera.projection);
+            }
+            Event::KeyDown { keycode: Some(Keycode::N), repeat: false, .. } => {
+                self.denoise = !self.denoise;
+                println!("Denoiser: {}", if self.denoise { "on" } else { "off" });
+            }
+            Event::KeyDown { keycode: Some(Keycode::M), repeat: false, .. } => {
+                let id = self.screenshots.push(&self.camera, &self.render_config);
+                println!("Queued screenshot {}", id);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Escape), keymod, repeat: false, .. } => {
+                if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) { self.screenshots.cancel_all(); }
+                else { self.screenshots.cancel_active(); }
+            }
+            Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => self.show_screenshot = !self.show_screenshot,
+            Event::KeyDown { keycode: Some(Keycode::G), repeat: false, .. } => self.select_next_node(),
+            Event::KeyDown { keycode: Some(Keycode::L), repeat: false, .. } => self.reload_scene(),
             Event::MouseMotion { xrel : x, yrel: y, .. } => 
-                { self.camera.rotation.1 -= (y as f64) / 1000.0; 
-                    self.camera.rotation.0 -= (x as f64) / 1000.0 }
+                { self.camera.rotate_pitch(-(y as f64) / 1000.0);
+                    self.camera.rotate_yaw(-(x as f64) / 1000.0) }
             _ => {}
         }
         }
@@ -88,16 +215,16 @@ impl Interface {
 
         for k in keys { match k {
             Keycode::W => { self.camera.position = &self.camera.position +
-                &(&V3::FORWARD.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 20.0));
+                &(&V3::FORWARD.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 20.0));
             },
             Keycode::A => { self.camera.position = &self.camera.position +
-                &(&V3::LEFT.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 20.0));
+                &(&V3::LEFT.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 20.0));
             },
             Keycode::S => { self.camera.position = &self.camera.position +
-                &(&V3::BACK.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 20.0));
+                &(&V3::BACK.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 20.0));
             },
             Keycode::D => { self.camera.position = &self.camera.position +
-                &(&V3::RIGHT.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 20.0));
+                &(&V3::RIGHT.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 20.0));
             },
             Keycode::Space => { self.camera.position = &self.camera.position +
                 &(&V3::UP * (delta_time * 20.0));
@@ -112,25 +239,39 @@ impl Interface {
                 &(&V3::DOWN * (delta_time * 1.0));
             },
             Keycode::Up => { self.camera.position = &self.camera.position +
-                &(&V3::FORWARD.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 1.0));
+                &(&V3::FORWARD.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 1.0));
             },
             Keycode::Left => { self.camera.position = &self.camera.position +
-                &(&V3::LEFT.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 1.0));
+                &(&V3::LEFT.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 1.0));
             },
             Keycode::Down => { self.camera.position = &self.camera.position +
-                &(&V3::BACK.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 1.0));
+                &(&V3::BACK.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 1.0));
             },
             Keycode::Right => { self.camera.position = &self.camera.position +
-                &(&V3::RIGHT.rotate_y(&V3::ZERO, self.camera.rotation.0) * (delta_time * 1.0));
+                &(&V3::RIGHT.rotate_y(&V3::ZERO, self.camera.get_yaw()) * (delta_time * 1.0));
+            },
+            Keycode::F => {
+                if let Some(distance) = rendering::get_closest_distance(&self.camera.get_centre_ray(), &self.render_config) {
+                    self.camera.focus_distance = distance;
+                    println!("Focus distance: {:.3}", distance);
+                }
             },
-            Keycode::M => { rendering::take_screenshot(&self.camera, &self.render_config, &mut self.rng); },
+            Keycode::Z => { self.camera.rotate_roll(delta_time); },
+            Keycode::X => { self.camera.rotate_roll(-delta_time); },
+            Keycode::Kp8 => { self.edit_selected_node(&(V3::FORWARD.rotate_y(&V3::ZERO, self.camera.get_yaw()) * delta_time), 0.0); },
+            Keycode::Kp2 => { self.edit_selected_node(&(V3::BACK.rotate_y(&V3::ZERO, self.camera.get_yaw()) * delta_time), 0.0); },
+            Keycode::Kp4 => { self.edit_selected_node(&(V3::LEFT.rotate_y(&V3::ZERO, self.camera.get_yaw()) * delta_time), 0.0); },
+            Keycode::Kp6 => { self.edit_selected_node(&(V3::RIGHT.rotate_y(&V3::ZERO, self.camera.get_yaw()) * delta_time), 0.0); },
+            Keycode::Kp9 => { self.edit_selected_node(&(V3::UP * delta_time), 0.0); },
+            Keycode::Kp3 => { self.edit_selected_node(&(V3::DOWN * delta_time), 0.0); },
+            Keycode::Kp7 => { self.edit_selected_node(&V3::ZERO, delta_time); },
+            Keycode::Kp1 => { self.edit_selected_node(&V3::ZERO, -delta_time); },
+            Keycode::LeftBracket => { self.camera.aperture_radius = (self.camera.aperture_radius - (delta_time * 0.1)).max(0.0); },
+            Keycode::RightBracket => { self.camera.aperture_radius += delta_time * 0.1; },
             _ => {}
         }
         }
 
-        if self.camera.rotation.1 > (PI / 2.0) { self.camera.rotation.1 = PI / 2.0; }
-        else if self.camera.rotation.1 < -(PI / 2.0) { self.camera.rotation.1 = -(PI / 2.0); }
-
         false
         }
 }
\ No newline at end of file
@@ -2,11 +2,158 @@ pub mod directional_light;
 pub use directional_light::DirectionalLight;
 pub mod point_light;
 pub use point_light::PointLight;
+pub mod rect_light;
+pub use rect_light::RectLight;
+pub mod disc_light;
+pub use disc_light::DiscLight;
+pub mod sphere_light;
+pub use sphere_light::SphereLight;
+pub mod spot_light;
+pub use spot_light::SpotLight;
+pub mod environment_light;
+pub use environment_light::EnvironmentLight;
+pub mod object_light;
+pub use object_light::ObjectLight;
+pub mod animated_light;
+pub use animated_light::AnimatedLight;
+pub mod transformed_light;
+pub use transformed_light::TransformedLight;
 
-use crate::{colour::Colour, maths::{lines::SizedLine, vectors::V3}};
+use std::sync::Arc;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{colour::Colour, maths::{lines::{Line, SizedLine}, vectors::V3}};
+
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    pub point: V3,
+    pub direction: V3, // Normalised, from the light towards the destination
+    pub distance: f64,
+    pub pdf: f64, // Per unit solid angle at the destination, 1 for point-like lights
+}
+
+impl LightSample {
+    pub fn new(point: &V3, direction: &V3, distance: f64, pdf: f64) -> Self {
+        Self { point: point.clone(), direction: direction.normalised(), distance, pdf }
+    }
+
+    pub fn none() -> Self {
+        Self { point: V3::ZERO, direction: V3::ZERO, distance: 0.0, pdf: 0.0 }
+    }
+}
 
 pub trait Light {
     fn get_direct_ray(&self, destination: &V3) -> SizedLine;
     fn get_intensity(&self, distance: f64) -> f64;
     fn get_colour(&self) -> &Colour;
+
+    fn sample(&self, destination: &V3, _rng: &mut dyn Sampler) -> LightSample {
+        let ray = self.get_direct_ray(destination);
+        LightSample::new(&ray.line.point, &ray.line.vector, ray.length(), 1.0)
+    }
+
+    fn get_sample_intensity(&self, sample: &LightSample) -> f64 {
+        self.get_intensity(sample.distance)
+    }
+
+    fn get_sample_colour(&self, _sample: &LightSample) -> Colour {
+        *self.get_colour()
+    }
+
+    // Where a ray from 'line.point' would hit the light - as if it had been returned by 'sample'
+    fn intersect(&self, _line: &Line) -> Option<LightSample> { None }
+
+    // Rough estimate of how much light this emits, used to decide which lights to sample
+    fn get_power(&self) -> f64 {
+        self.get_colour().luminance() * self.get_intensity(1.0)
+    }
+
+    // Axis aligned (min, max), None for lights at infinity
+    fn get_bounds(&self) -> Option<(V3, V3)> { None }
+
+    // Point-like lights give the same sample every time so only need one shadow ray
+    fn is_delta(&self) -> bool { true }
+
+    // Updates animated lights to how they are at 'time' seconds, through a shared reference so instances of them change too
+    fn set_time(&self, _time: f64) {}
+}
+
+// Lets a scene's shared lights be used directly when they don't need moving
+impl<T: Light + ?Sized> Light for Arc<T> {
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine { self.as_ref().get_direct_ray(destination) }
+    fn get_intensity(&self, distance: f64) -> f64 { self.as_ref().get_intensity(distance) }
+    fn get_colour(&self) -> &Colour { self.as_ref().get_colour() }
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample { self.as_ref().sample(destination, rng) }
+    fn get_sample_intensity(&self, sample: &LightSample) -> f64 { self.as_ref().get_sample_intensity(sample) }
+    fn get_sample_colour(&self, sample: &LightSample) -> Colour { self.as_ref().get_sample_colour(sample) }
+    fn intersect(&self, line: &Line) -> Option<LightSample> { self.as_ref().intersect(line) }
+    fn get_power(&self) -> f64 { self.as_ref().get_power() }
+    fn get_bounds(&self) -> Option<(V3, V3)> { self.as_ref().get_bounds() }
+    fn is_delta(&self) -> bool { self.as_ref().is_delta() }
+    fn set_time(&self, time: f64) { self.as_ref().set_time(time) }
+}
+
+// Checks shared by the tests of each light
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::PI;
+
+    use crate::colour::Colour;
+    use crate::maths::{lines::Line, vectors::V3, sampler::{Sampler, IndependentSampler}};
+
+    use super::{Light, RectLight, DiscLight, SphereLight};
+
+    // Rays back along each sample must find the same point with the same pdf
+    pub fn assert_samples_match_intersect(light: &dyn Light, destination: &V3) {
+        let mut rng = IndependentSampler::new(0);
+
+        for i in 0..1000 {
+            rng.start_sample((0, 0), i);
+            let sample = light.sample(destination, &mut rng);
+            if sample.pdf <= 0.0 { continue; }
+
+            let found = light.intersect(&Line::new(destination, &(sample.direction * -1.0))).expect("sample missed the light");
+            assert!((found.point - sample.point).magnitude() < 1e-6, "{:?} vs {:?}", found.point, sample.point);
+            assert!((found.pdf - sample.pdf).abs() < 1e-6 * sample.pdf, "{} vs {}", found.pdf, sample.pdf);
+        }
+    }
+
+    // The pdf of the directions that hit the light, integrated over the sphere of directions
+    pub fn assert_pdf_integrates_to_one(light: &dyn Light, destination: &V3) {
+        let mut rng = IndependentSampler::new(1);
+        let count = 100000;
+
+        let total: f64 = (0..count).map(|i| {
+            rng.start_sample((0, 0), i);
+            let direction = V3::get_random(&V3::UP, PI, &mut rng);
+            light.intersect(&Line::new(destination, &direction)).map_or(0.0, |sample| sample.pdf)
+        }).sum();
+
+        let integral = 4.0 * PI * total / count as f64;
+        assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+    }
+
+    // Area lights above the origin, each facing it
+    fn get_area_lights() -> Vec<Box<dyn Light>> {
+        vec![
+            Box::new(RectLight::new(&V3::new(0.3, 1.0, -0.2), &(V3::FORWARD * 2.0), &(V3::RIGHT * 1.5), &Colour::WHITE, 10.0)),
+            Box::new(DiscLight::new(&V3::new(0.3, 1.0, -0.2), &V3::new(0.3, -1.0, 0.2), 1.0, &Colour::WHITE, 10.0)),
+            Box::new(SphereLight::new(&V3::new(0.3, 2.0, -0.2), 1.0, &Colour::WHITE, 10.0)),
+        ]
+    }
+
+    #[test]
+    fn area_light_samples_match_their_pdf() {
+        for light in get_area_lights() {
+            assert_samples_match_intersect(light.as_ref(), &V3::ZERO);
+        }
+    }
+
+    #[test]
+    fn area_light_pdfs_integrate_to_one() {
+        for light in get_area_lights() {
+            assert_pdf_integrates_to_one(light.as_ref(), &V3::ZERO);
+        }
+    }
 }
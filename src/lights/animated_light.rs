@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::animation::Track;
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}}, colour::Colour};
+
+use super::{Light, LightSample};
+
+// Scales another light's intensity over time
+#[allow(dead_code)]
+pub struct AnimatedLight {
+    light: Box<dyn Light + Sync + Send>,
+    pub intensity: Track<f64>, // Multiplier on the light's own intensity
+    time: AtomicU64, // f64 bits
+}
+
+#[allow(dead_code)]
+impl AnimatedLight {
+    pub fn new(light: Box<dyn Light + Sync + Send>, intensity: Track<f64>) -> Self {
+        Self { light, intensity, time: AtomicU64::new(0.0f64.to_bits()) }
+    }
+
+    fn get_multiplier(&self) -> f64 {
+        self.intensity.get(f64::from_bits(self.time.load(Ordering::Relaxed))).unwrap_or(1.0).max(0.0)
+    }
+}
+
+impl Light for AnimatedLight {
+    fn get_colour(&self) -> &Colour {
+        self.light.get_colour()
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        self.light.get_direct_ray(destination)
+    }
+
+    fn get_intensity(&self, distance: f64) -> f64 {
+        self.light.get_intensity(distance) * self.get_multiplier()
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        self.light.sample(destination, rng)
+    }
+
+    fn get_sample_intensity(&self, sample: &LightSample) -> f64 {
+        self.light.get_sample_intensity(sample) * self.get_multiplier()
+    }
+
+    fn get_sample_colour(&self, sample: &LightSample) -> Colour {
+        self.light.get_sample_colour(sample)
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        self.light.intersect(line)
+    }
+
+    fn get_power(&self) -> f64 {
+        self.light.get_power() * self.get_multiplier()
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        self.light.get_bounds()
+    }
+
+    fn is_delta(&self) -> bool {
+        self.light.is_delta()
+    }
+
+    fn set_time(&self, time: f64) {
+        self.light.set_time(time);
+        self.time.store(time.to_bits(), Ordering::Relaxed);
+    }
+}
@@ -1,8 +1,8 @@
-use crate::{maths::{vectors::V3, lines::SizedLine}, colour::Colour};
+use crate::maths::sampler::Sampler;
 
-use super::Light;
+use crate::{maths::{vectors::V3, lines::SizedLine}, colour::Colour};
 
-const INFINITY: f64 = 1_000_000.0;
+use super::{Light, LightSample};
 
 pub struct DirectionalLight {
     direction: V3,
@@ -12,13 +12,13 @@ pub struct DirectionalLight {
 
 impl DirectionalLight {
     pub fn new(direction: &V3, colour: &Colour, intensity: f64) -> Self {
-        Self { direction: direction.clone(), colour: colour.clone(), intensity }
+        Self { direction: direction.normalised(), colour: *colour, intensity }
     }
 }
 
 impl Light for DirectionalLight {
     fn get_direct_ray(&self, destination: &V3) -> SizedLine {
-        SizedLine::new(&(destination - (self.direction * INFINITY)), &self.direction, INFINITY)
+        SizedLine::new(&(destination - self.direction), &self.direction, 1.0)
     }
 
     fn get_intensity(&self, _distance: f64) -> f64 {
@@ -28,4 +28,13 @@ impl Light for DirectionalLight {
     fn get_colour(&self) -> &Colour {
         &self.colour
     }
-}
\ No newline at end of file
+
+    fn get_power(&self) -> f64 {
+        self.colour.luminance() * self.intensity
+    }
+
+    fn sample(&self, destination: &V3, _rng: &mut dyn Sampler) -> LightSample {
+        // The light is infinitely far away so anything in the way casts a shadow
+        LightSample::new(&(destination - self.direction), &self.direction, f64::INFINITY, 1.0)
+    }
+}
@@ -0,0 +1,87 @@
+use std::f64::consts::PI;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}}, colour::Colour};
+
+use super::{Light, LightSample};
+
+// One-sided, emitting towards 'normal'
+#[allow(dead_code)]
+pub struct DiscLight {
+    position: V3,
+    normal: V3,
+    radius: f64,
+    colour: Colour,
+    intensity: f64
+}
+
+#[allow(dead_code)]
+impl DiscLight {
+    pub fn new(position: &V3, normal: &V3, radius: f64, colour: &Colour, intensity: f64) -> Self {
+        Self { position: position.clone(), normal: normal.normalised(), radius, colour: *colour, intensity }
+    }
+
+    fn get_area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl Light for DiscLight {
+    fn get_colour(&self) -> &Colour {
+        &self.colour
+    }
+
+    fn get_power(&self) -> f64 {
+        self.colour.luminance() * self.intensity
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        let extent = V3::ONE * self.radius;
+        Some((self.position - extent, self.position + extent))
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        let vector = destination - self.position;
+        SizedLine::new(&self.position, &vector, 1.0)
+    }
+
+    fn get_intensity(&self, _distance: f64) -> f64 {
+        self.intensity / (PI * self.get_area())
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        let (b1, b2) = self.normal.get_basis();
+        let (u, v) = rng.get_2d();
+        let r = self.radius * u.sqrt();
+        let theta = (2.0 * v - 1.0) * PI;
+
+        let point = self.position + (b1 * (r * theta.cos())) + (b2 * (r * theta.sin()));
+        let vector = destination - point;
+        let distance = vector.magnitude();
+
+        let cos_light = self.normal.cos_angle_to(&vector);
+        if cos_light <= 0.0 { return LightSample::none(); }
+
+        LightSample::new(&point, &vector, distance, (distance * distance) / (self.get_area() * cos_light))
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        let denominator = line.vector.dot(&self.normal);
+        if denominator >= 0.0 { return None; } // Parallel or hitting the back
+
+        let scale = (self.position - line.point).dot(&self.normal) / denominator;
+        if scale <= 0.0 { return None; }
+
+        let point = line.scale(scale);
+        if (point - self.position).magnitude() > self.radius { return None; }
+
+        let vector = line.point - point;
+        let distance = vector.magnitude();
+        let cos_light = self.normal.cos_angle_to(&vector);
+
+        Some(LightSample::new(&point, &vector, distance, (distance * distance) / (self.get_area() * cos_light)))
+    }
+
+    fn is_delta(&self) -> bool { false }
+}
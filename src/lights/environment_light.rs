@@ -0,0 +1,69 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}}, colour::Colour, environment::Environment};
+
+use super::{Light, LightSample};
+
+// Lights the scene from an environment at infinity - an intensity of 1 matches the background
+#[allow(dead_code)]
+pub struct EnvironmentLight {
+    environment: Arc<dyn Environment + Sync + Send>,
+    colour: Colour,
+    intensity: f64
+}
+
+#[allow(dead_code)]
+impl EnvironmentLight {
+    pub fn new(environment: Arc<dyn Environment + Sync + Send>, intensity: f64) -> Self {
+        Self { environment, colour: Colour::WHITE, intensity }
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn get_colour(&self) -> &Colour {
+        &self.colour
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        SizedLine::new(&(destination + V3::UP), &V3::DOWN, 1.0)
+    }
+
+    fn get_intensity(&self, _distance: f64) -> f64 {
+        self.intensity
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        let (direction, pdf) = self.environment.sample(rng);
+        if pdf <= 0.0 { return LightSample::none(); }
+
+        LightSample::new(&(destination + direction), &(direction * -1.0), f64::INFINITY, pdf)
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        let direction = line.vector.normalised();
+        Some(LightSample::new(&(line.point + direction), &(direction * -1.0), f64::INFINITY, self.environment.get_pdf(&direction)))
+    }
+
+    // Irradiance from the average radiance in a spread of directions
+    fn get_power(&self) -> f64 {
+        const COUNT: usize = 64;
+
+        let total: f64 = (0..COUNT).map(|i| {
+            // Fibonacci sphere
+            let y = 1.0 - (2.0 * (i as f64 + 0.5) / COUNT as f64);
+            let r = (1.0 - y * y).sqrt();
+            let phi = i as f64 * PI * (3.0 - 5.0f64.sqrt());
+            self.environment.get_colour(&V3::new(r * phi.cos(), y, r * phi.sin())).luminance()
+        }).sum();
+
+        PI * self.intensity * total / COUNT as f64
+    }
+
+    fn get_sample_colour(&self, sample: &LightSample) -> Colour {
+        self.environment.get_colour(&(sample.direction * -1.0))
+    }
+
+    fn is_delta(&self) -> bool { false }
+}
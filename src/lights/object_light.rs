@@ -0,0 +1,70 @@
+use std::f64::consts::PI;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}, Intersection}, colour::Colour, objects::Object};
+
+use super::{Light, LightSample};
+
+// Lets an emissive object be sampled like any other area light
+pub struct ObjectLight<'a> {
+    object: &'a (dyn Object + Sync),
+    emission: Colour,
+}
+
+impl<'a> ObjectLight<'a> {
+    pub fn new(object: &'a (dyn Object + Sync)) -> Self {
+        Self { object, emission: object.get_surface_type().emission }
+    }
+}
+
+impl<'a> Light for ObjectLight<'a> {
+    fn get_colour(&self) -> &Colour {
+        &self.emission
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        SizedLine::new(destination, &V3::ZERO, 0.0)
+    }
+
+    fn get_power(&self) -> f64 {
+        self.emission.luminance() * self.object.get_surface_area() * PI
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        self.object.get_bounds()
+    }
+
+    fn get_intensity(&self, _distance: f64) -> f64 {
+        // The emission is already radiance
+        1.0
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        let sample = self.object.get_random_point(destination, rng);
+        if sample.is_none() { return LightSample::none(); }
+        let (point, normal, pdf) = sample.unwrap();
+
+        let vector = destination - point;
+        let distance = vector.magnitude();
+        let cos_light = normal.cos_angle_to(&vector);
+        if cos_light <= 0.0 { return LightSample::none(); }
+
+        LightSample::new(&point, &vector, distance, pdf * distance * distance / cos_light)
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        let hits = self.object.get_intersections(line);
+        let hit = Intersection::closest(&hits)?;
+
+        let vector = line.point - hit.position;
+        let distance = vector.magnitude();
+        let cos_light = self.object.get_normal(hit).cos_angle_to(&vector);
+        if cos_light <= 0.0 { return None; }
+
+        let pdf = self.object.get_random_point_pdf(&line.point, &hit.position);
+        Some(LightSample::new(&hit.position, &vector, distance, pdf * distance * distance / cos_light))
+    }
+
+    fn is_delta(&self) -> bool { false }
+}
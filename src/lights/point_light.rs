@@ -4,12 +4,14 @@ use crate::{maths::{vectors::V3, lines::SizedLine}, colour::Colour};
 
 use super::Light;
 
+#[allow(dead_code)]
 pub struct PointLight {
     position: V3,
     colour: Colour,
     intensity: f64
 }
 
+#[allow(dead_code)]
 impl PointLight {
     pub fn new(position: &V3, colour: &Colour, intensity: f64) -> Self {
         Self { position: position.clone(), colour: colour.clone(), intensity }
@@ -21,6 +23,14 @@ impl Light for PointLight {
         &self.colour
     }
 
+    fn get_power(&self) -> f64 {
+        self.colour.luminance() * self.intensity
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        Some((self.position, self.position))
+    }
+
     fn get_direct_ray(&self, destination: &V3) -> SizedLine {
         let vector = destination - self.position;
         SizedLine::new(&self.position, &vector, 1.0)
@@ -0,0 +1,93 @@
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}}, colour::Colour};
+
+use super::{Light, LightSample};
+
+// One-sided, emitting towards vector_one x vector_two
+#[allow(dead_code)]
+pub struct RectLight {
+    position: V3,
+    vector_one: V3,
+    vector_two: V3,
+    colour: Colour,
+    intensity: f64
+}
+
+#[allow(dead_code)]
+impl RectLight {
+    pub fn new(position: &V3, vector_one: &V3, vector_two: &V3, colour: &Colour, intensity: f64) -> Self {
+        Self { position: position.clone(), vector_one: vector_one.clone(), vector_two: vector_two.clone(), colour: *colour, intensity }
+    }
+
+    fn get_area(&self) -> f64 {
+        self.vector_one.cross(&self.vector_two).magnitude()
+    }
+}
+
+impl Light for RectLight {
+    fn get_colour(&self) -> &Colour {
+        &self.colour
+    }
+
+    fn get_power(&self) -> f64 {
+        self.colour.luminance() * self.intensity
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        let corners = [
+            self.position + ((self.vector_one + self.vector_two) * 0.5),
+            self.position + ((self.vector_one - self.vector_two) * 0.5),
+            self.position - ((self.vector_one + self.vector_two) * 0.5),
+            self.position - ((self.vector_one - self.vector_two) * 0.5),
+        ];
+
+        Some(corners.iter().fold((corners[0], corners[0]), |b, c| (b.0.min(c), b.1.max(c))))
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        let vector = destination - self.position;
+        SizedLine::new(&self.position, &vector, 1.0)
+    }
+
+    fn get_intensity(&self, _distance: f64) -> f64 {
+        // Radiance of a lambertian emitter with total power 'intensity'
+        self.intensity / (std::f64::consts::PI * self.get_area())
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        let (u, v) = rng.get_2d();
+        let point = self.position + (self.vector_one * (u - 0.5)) + (self.vector_two * (v - 0.5));
+        let vector = destination - point;
+        let distance = vector.magnitude();
+
+        let cos_light = self.vector_one.cross(&self.vector_two).cos_angle_to(&vector);
+        if cos_light <= 0.0 { return LightSample::none(); }
+
+        LightSample::new(&point, &vector, distance, (distance * distance) / (self.get_area() * cos_light))
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        let normal = self.vector_one.cross(&self.vector_two);
+        let denominator = line.vector.dot(&normal);
+        if denominator >= 0.0 { return None; } // Parallel or hitting the back
+
+        let scale = (self.position - line.point).dot(&normal) / denominator;
+        if scale <= 0.0 { return None; }
+
+        // Edges are assumed perpendicular
+        let point = line.scale(scale);
+        let offset = point - self.position;
+        let u = offset.dot(&self.vector_one) / self.vector_one.dot(&self.vector_one);
+        let v = offset.dot(&self.vector_two) / self.vector_two.dot(&self.vector_two);
+        if u.abs() > 0.5 || v.abs() > 0.5 { return None; }
+
+        let vector = line.point - point;
+        let distance = vector.magnitude();
+        let cos_light = normal.cos_angle_to(&vector);
+
+        Some(LightSample::new(&point, &vector, distance, (distance * distance) / (self.get_area() * cos_light)))
+    }
+
+    fn is_delta(&self) -> bool { false }
+}
@@ -0,0 +1,83 @@
+use std::f64::consts::PI;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}}, colour::Colour};
+
+use super::{Light, LightSample};
+
+#[allow(dead_code)]
+pub struct SphereLight {
+    position: V3,
+    radius: f64,
+    colour: Colour,
+    intensity: f64
+}
+
+#[allow(dead_code)]
+impl SphereLight {
+    pub fn new(position: &V3, radius: f64, colour: &Colour, intensity: f64) -> Self {
+        Self { position: position.clone(), radius, colour: *colour, intensity }
+    }
+}
+
+impl Light for SphereLight {
+    fn get_colour(&self) -> &Colour {
+        &self.colour
+    }
+
+    fn get_power(&self) -> f64 {
+        self.colour.luminance() * self.intensity
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        let extent = V3::ONE * self.radius;
+        Some((self.position - extent, self.position + extent))
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        let vector = destination - self.position;
+        SizedLine::new(&self.position, &vector, 1.0)
+    }
+
+    fn get_intensity(&self, _distance: f64) -> f64 {
+        self.intensity / (4.0 * PI * PI * self.radius * self.radius)
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        // Sample the cone of directions the sphere covers as seen from 'destination'
+        let to_centre = self.position - destination;
+        let centre_distance_squared = to_centre.dot(&to_centre);
+        if centre_distance_squared <= self.radius * self.radius { return LightSample::none(); }
+
+        let cos_max = (1.0 - (self.radius * self.radius / centre_distance_squared)).sqrt();
+        let direction = V3::get_random(&to_centre, cos_max.acos(), rng);
+
+        // Nearest intersection of the sampled direction with the sphere
+        let b = direction.dot(&to_centre);
+        let under_root = (b * b) - centre_distance_squared + (self.radius * self.radius);
+        let distance = b - under_root.max(0.0).sqrt();
+        let point = destination + (direction * distance);
+
+        LightSample::new(&point, &(direction * -1.0), distance, 1.0 / (2.0 * PI * (1.0 - cos_max)))
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        let to_centre = self.position - line.point;
+        let centre_distance_squared = to_centre.dot(&to_centre);
+        if centre_distance_squared <= self.radius * self.radius { return None; }
+
+        let direction = line.vector.normalised();
+        let b = direction.dot(&to_centre);
+        let under_root = (b * b) - centre_distance_squared + (self.radius * self.radius);
+        if b <= 0.0 || under_root < 0.0 { return None; }
+
+        let distance = b - under_root.sqrt();
+        let point = line.point + (direction * distance);
+        let cos_max = (1.0 - (self.radius * self.radius / centre_distance_squared)).sqrt();
+
+        Some(LightSample::new(&point, &(direction * -1.0), distance, 1.0 / (2.0 * PI * (1.0 - cos_max))))
+    }
+
+    fn is_delta(&self) -> bool { false }
+}
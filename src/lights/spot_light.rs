@@ -0,0 +1,142 @@
+use std::f64::consts::PI;
+
+use crate::{maths::{vectors::V3, lines::SizedLine}, colour::{Colour, colour_getters::ColourGetter}};
+
+use super::{Light, LightSample};
+
+#[allow(dead_code)]
+pub struct SpotLight {
+    position: V3,
+    direction: V3,
+    inner_angle: f64,
+    outer_angle: f64,
+    colour: Colour,
+    intensity: f64,
+    gobo: Option<Box<dyn ColourGetter + Sync + Send>>,
+}
+
+#[allow(dead_code)]
+impl SpotLight {
+    pub fn new(position: &V3, direction: &V3, inner_angle: f64, outer_angle: f64, colour: &Colour, intensity: f64) -> Self {
+        Self { position: position.clone(), direction: direction.normalised(), inner_angle, outer_angle, colour: *colour, intensity, gobo: None }
+    }
+
+    pub fn with_gobo(mut self, gobo: Box<dyn ColourGetter + Sync + Send>) -> Self {
+        self.gobo = Some(gobo);
+        self
+    }
+
+    fn get_falloff(&self, vector: &V3) -> f64 {
+        let cos_angle = self.direction.cos_angle_to(vector);
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.cos();
+
+        if cos_angle <= cos_outer { return 0.0; }
+        if cos_angle >= cos_inner { return 1.0; }
+
+        // Smoothstep between the outer and inner cones
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn get_colour(&self) -> &Colour {
+        &self.colour
+    }
+
+    fn get_power(&self) -> f64 {
+        self.colour.luminance() * self.intensity
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        Some((self.position, self.position))
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        let vector = destination - self.position;
+        SizedLine::new(&self.position, &vector, 1.0)
+    }
+
+    fn get_intensity(&self, distance: f64) -> f64 {
+        self.intensity / (distance.powi(2) * 4.0 * PI)
+    }
+
+    fn get_sample_intensity(&self, sample: &LightSample) -> f64 {
+        self.get_intensity(sample.distance) * self.get_falloff(&sample.direction)
+    }
+
+    fn get_sample_colour(&self, sample: &LightSample) -> Colour {
+        if self.gobo.is_none() { return self.colour; }
+
+        // Project the direction onto a plane in front of the light covering the outer cone
+        let (b1, b2) = self.direction.get_basis();
+        let forward = sample.direction.dot(&self.direction);
+        if forward <= 0.0 { return Colour::BLACK; }
+
+        let extent = self.outer_angle.tan() * forward;
+        let position = (0.5 + (0.5 * sample.direction.dot(&b1) / extent), 0.5 + (0.5 * sample.direction.dot(&b2) / extent));
+
+        self.colour * self.gobo.as_ref().unwrap().get_colour(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::colour::{Colour, colour_getters::ColourGetter};
+    use crate::lights::Light;
+    use crate::maths::{vectors::V3, sampler::IndependentSampler};
+
+    use super::SpotLight;
+
+    // Red on one side of the beam and blue on the other
+    struct SplitGobo;
+
+    impl ColourGetter for SplitGobo {
+        fn get_colour(&self, position: (f64, f64)) -> &Colour {
+            if position.0 < 0.5 { &Colour::RED } else { &Colour::BLUE }
+        }
+    }
+
+    fn get_light() -> SpotLight {
+        SpotLight::new(&V3::ZERO, &V3::DOWN, 0.3, 0.5, &Colour::WHITE, 4.0 * PI)
+    }
+
+    // Intensity reaching a point 'distance' away, 'angle' radians off the axis of the light
+    fn get_intensity_at(light: &SpotLight, angle: f64, distance: f64) -> f64 {
+        let destination = V3::new(angle.sin(), -angle.cos(), 0.0) * distance;
+        light.get_sample_intensity(&light.sample(&destination, &mut IndependentSampler::new(0)))
+    }
+
+    #[test]
+    fn falloff_blends_from_the_inner_to_the_outer_cone() {
+        let light = get_light();
+        let intensities: Vec<f64> = [0.0, 0.2, 0.35, 0.4, 0.45, 0.6].iter().map(|angle| get_intensity_at(&light, *angle, 1.0)).collect();
+
+        assert!((intensities[0] - 1.0).abs() < 1e-9 && (intensities[1] - 1.0).abs() < 1e-9, "{:?}", intensities);
+        assert!(intensities[2] > intensities[3] && intensities[3] > intensities[4] && intensities[4] > 0.0, "{:?}", intensities);
+        assert_eq!(intensities[5], 0.0);
+    }
+
+    #[test]
+    fn intensity_follows_the_inverse_square_law() {
+        let light = get_light();
+        assert!((get_intensity_at(&light, 0.1, 2.0) - 0.25).abs() < 1e-9);
+        assert!((get_intensity_at(&light, 0.1, 4.0) - 0.0625).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gobo_is_projected_across_the_beam() {
+        let light = get_light().with_gobo(Box::new(SplitGobo));
+        let (b1, _) = V3::DOWN.get_basis();
+        let mut rng = IndependentSampler::new(0);
+
+        let one_side = light.get_sample_colour(&light.sample(&(V3::DOWN + (b1 * 0.2)), &mut rng));
+        let other_side = light.get_sample_colour(&light.sample(&(V3::DOWN - (b1 * 0.2)), &mut rng));
+
+        assert_eq!(one_side.as_f64(), Colour::BLUE.as_f64());
+        assert_eq!(other_side.as_f64(), Colour::RED.as_f64());
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use crate::maths::sampler::Sampler;
+
+use crate::{maths::{vectors::V3, lines::{Line, SizedLine}, transform::Transform}, colour::Colour};
+
+use super::{Light, LightSample};
+
+// Shared light placed by a transform, like an object instance
+// A uniform scale stretches a light's samples and distances alike, so their solid angle pdfs carry over unchanged
+// Non-uniform scales would change them differently across the light, so they aren't allowed
+#[allow(dead_code)]
+pub struct TransformedLight {
+    light: Arc<dyn Light + Sync + Send>,
+    transform: Transform, // Light space to world space
+}
+
+#[allow(dead_code)]
+impl TransformedLight {
+    // None if 'transform' scales non-uniformly
+    pub fn new(light: Arc<dyn Light + Sync + Send>, transform: Transform) -> Option<Self> {
+        transform.get_uniform_scale()?;
+        Some(Self { light, transform })
+    }
+
+    fn to_world_sample(&self, sample: &LightSample, destination: &V3) -> LightSample {
+        if sample.pdf == 0.0 { return *sample; }
+
+        // Lights at infinity only have a direction
+        if sample.distance.is_infinite() {
+            let direction = self.transform.transform_vector(&sample.direction).normalised();
+            return LightSample::new(&(destination - direction), &direction, f64::INFINITY, sample.pdf);
+        }
+
+        let point = self.transform.transform_point(&sample.point);
+        LightSample::new(&point, &(destination - point), (destination - point).magnitude(), sample.pdf)
+    }
+
+    fn to_local_sample(&self, sample: &LightSample) -> LightSample {
+        LightSample {
+            point: self.transform.inverse_transform_point(&sample.point),
+            direction: self.transform.inverse_transform_vector(&sample.direction).normalised(),
+            distance: sample.distance,
+            pdf: sample.pdf,
+        }
+    }
+}
+
+impl Light for TransformedLight {
+    fn get_colour(&self) -> &Colour {
+        self.light.get_colour()
+    }
+
+    fn get_direct_ray(&self, destination: &V3) -> SizedLine {
+        let ray = self.light.get_direct_ray(&self.transform.inverse_transform_point(destination));
+        SizedLine::new(&self.transform.transform_point(&ray.line.point), &self.transform.transform_vector(&ray.line.vector), ray.scale)
+    }
+
+    fn get_intensity(&self, distance: f64) -> f64 {
+        self.light.get_intensity(distance)
+    }
+
+    fn sample(&self, destination: &V3, rng: &mut dyn Sampler) -> LightSample {
+        let sample = self.light.sample(&self.transform.inverse_transform_point(destination), rng);
+        self.to_world_sample(&sample, destination)
+    }
+
+    fn get_sample_intensity(&self, sample: &LightSample) -> f64 {
+        self.light.get_sample_intensity(&self.to_local_sample(sample))
+    }
+
+    fn get_sample_colour(&self, sample: &LightSample) -> Colour {
+        self.light.get_sample_colour(&self.to_local_sample(sample))
+    }
+
+    fn intersect(&self, line: &Line) -> Option<LightSample> {
+        let local = Line::new(&self.transform.inverse_transform_point(&line.point), &self.transform.inverse_transform_vector(&line.vector)).at_time(line.time);
+        let sample = self.light.intersect(&local)?;
+        Some(self.to_world_sample(&sample, &line.point))
+    }
+
+    fn get_power(&self) -> f64 {
+        self.light.get_power()
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        Some(self.transform.transform_bounds(&self.light.get_bounds()?))
+    }
+
+    fn is_delta(&self) -> bool {
+        self.light.is_delta()
+    }
+
+    fn set_time(&self, time: f64) {
+        self.light.set_time(time);
+    }
+}
@@ -1,9 +1,14 @@
+use std::sync::{Arc, OnceLock};
+
 use colour::{Colour, colour_getters::{Texture, SolidColour}};
+use environment::PreethamSky;
 use interface::Interface;
-use lights::{Light, DirectionalLight, PointLight};
-use maths::vectors::V3;
-use objects::{Sphere, SurfaceType, Object, Plane};
-use rendering::{RenderConfig, Camera};
+use lights::{RectLight, SpotLight, AnimatedLight};
+use maths::{vectors::V3, sampler::SamplerType, transform::Transform};
+use objects::{Sphere, SurfaceType, Object, Plane, AnimatedObject, Csg};
+use rendering::{RenderConfig, Camera, LightSampling, LightSelection, Filter, Denoiser, CancellationToken, RenderTarget, CheckpointHeader};
+use animation::{Track, Interpolation, CameraTrack};
+use scene::{SceneNode, scene_file};
 
 mod maths;
 mod colour;
@@ -11,6 +16,9 @@ mod objects;
 mod rendering;
 mod interface;
 mod lights;
+mod environment;
+mod animation;
+mod scene;
 
 const _16K: (u32, u32) = (15360, 8640);
 const _8K: (u32, u32) = (7680, 4320);
@@ -24,88 +32,82 @@ const _240P: (u32, u32) = (320, 240);
 const _144P: (u32, u32) = (192, 144);
 
 fn main() {
-    let scene_objects: Vec<Box<dyn Object + Sync>> = vec![
-        // Floor
-        Box::new(Plane::new(
+    let mut scene = SceneNode::group("Scene")
+        .with_child(SceneNode::object("Floor", Arc::new(Plane::new(
             &V3::new(0.0, 0.0, 0.0), 
             &(&V3::FORWARD * 5.0), 
             &(&V3::RIGHT * 5.0), 
             None, 
             Box::new(Texture::new("static/textures/prototype2.png").unwrap()),
             SurfaceType::new(0.0, 0.0, 0.0, 1.0, true, false),
-        )),
-        
-        // Mirror
-        Box::new(Plane::new(
+        ))))
+        .with_child(SceneNode::object("Mirror", Arc::new(Plane::new(
             &V3::new(7.0, 8.0, 0.0), &V3::new(1.0, 0.0, 1.0).normalised(), &V3::new(-1.0, 6.0, 1.0).normalised(), 
             Some((-6.0, -4.0, 6.0, 4.0)), 
             Box::new(SolidColour { colour: Colour::BLACK }),
             SurfaceType::new(0.0, 0.7, 0.0, 1.54, true, false)
-        )),
-
-        // Spheres
-        Box::new(Sphere::new(V3::new(2.0 , 1.0, 0.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0 , 1.0, 4.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0 , 1.0, 2.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0, 1.0, 4.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0 , 1.0, 2.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0, 1.0, 0.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0 , 1.0, 0.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(0.0 , 1.0, 4.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(-2.0, 1.0, 2.0), 0.5, Colour::BLACK, SurfaceType::new(0.0, 0.8, 0.0, 1.52, false, false))),
-
-        Box::new(Sphere::new(V3::new(2.0 , 1.0, 0.0 + 6.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0 , 1.0, 4.0 + 6.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0 , 1.0, 2.0 + 6.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0, 1.0, 4.0 + 6.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0 , 1.0, 2.0 + 6.0), 0.5, Colour::WHITE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0, 1.0, 0.0 + 6.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0 , 1.0, 0.0 + 6.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(0.0 , 1.0, 4.0 + 6.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(-2.0, 1.0, 2.0 + 6.0), 0.5, Colour::BLACK, SurfaceType::new(0.0, 0.8, 0.0, 1.52, false, false))),
-
-        Box::new(Sphere::new(V3::new(2.0  + 6.0, 1.0, 0.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0  + 6.0, 1.0, 4.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0  + 6.0, 1.0, 2.0), 0.5, Colour::WHITE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0 + 6.0, 1.0, 4.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0  + 6.0, 1.0, 2.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0 + 6.0, 1.0, 0.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0  + 6.0, 1.0, 0.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(0.0  + 6.0, 1.0, 4.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(-2.0 + 6.0, 1.0, 2.0), 0.5, Colour::BLACK, SurfaceType::new(0.0, 0.8, 0.0, 1.52, false, false))),
-
-        Box::new(Sphere::new(V3::new(2.0  + 6.0, 1.0, 0.0 + 6.0), 0.5, Colour::WHITE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0  + 6.0, 1.0, 4.0 + 6.0), 0.5, Colour::RED, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(2.0  + 6.0, 1.0, 2.0 + 6.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0 + 6.0, 1.0, 4.0 + 6.0), 0.5, Colour::BLUE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0  + 6.0, 1.0, 2.0 + 6.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(-2.0 + 6.0, 1.0, 0.0 + 6.0), 0.5, Colour::GREEN, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false))),
-        Box::new(Sphere::new(V3::new(0.0  + 6.0, 1.0, 0.0 + 6.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(0.0  + 6.0, 1.0, 4.0 + 6.0), 0.5, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-        Box::new(Sphere::new(V3::new(-2.0 + 6.0, 1.0, 2.0 + 6.0), 0.5, Colour::BLACK, SurfaceType::new(0.0, 0.8, 0.0, 1.52, false, false))),
-
-        // Big Sphere
-        Box::new(Sphere::new(V3::new(3.0, 3.0, 5.0), 2.0, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
-
-        // Light Sphere
-        Box::new(Sphere::new(V3::new(1.5, 2.0, -1.5), 0.2, Colour::from_f64(1.0, 1.0, 0.0), SurfaceType::new(0.0, 0.0, 0.0, 1.52, false, true))),
-
-        // Walls
-        Box::new(Plane::new(&V3::new(10.0, 2.0, 4.0), &V3::new(0.0, 1.0, 0.0), &V3::new(0.0, 0.0, 1.0), Some((0.0, 0.0, 4.0, 4.0)), 
-            Box::new(SolidColour { colour: Colour::RED }),
-            SurfaceType::new(1.0, 0.0, 0.0, 1.54, true, false)
-        )),
-        Box::new(Plane::new(&V3::new(6.0, 6.0, 8.0), &V3::new(0.0, 1.0, 0.0), &V3::new(-1.0, 0.0, 0.0), Some((0.0, 0.0, 4.0, 4.0)), 
-            Box::new(SolidColour { colour: Colour::WHITE }),
-            SurfaceType::new(1.0, 0.0, 0.0, 1.54, true, false)
-        )),
-    ];
+        ))));
+
+    // Spheres - four copies of a block of nine, each with one sphere swapped for a white one
+    let diffuse = SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false);
+    let glass = SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false);
+    let red: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::RED, diffuse));
+    let blue: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::BLUE, diffuse));
+    let green: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::GREEN, diffuse));
+    let white: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::WHITE, diffuse));
+    let teal: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::from_u8(44, 90, 100), glass));
+    let mirror: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::BLACK, SurfaceType::new(0.0, 0.8, 0.0, 1.52, false, false)));
 
-    let scene_lights: Vec<Box<dyn Light + Sync>> = vec![
-        Box::new(DirectionalLight::new(&V3::new(1.0, -1.0, 1.0), &Colour::from_u8(255, 235, 200), 1.5)),
-        Box::new(DirectionalLight::new(&V3::new(0.0, -1.0, 0.0), &Colour::from_u8(205, 247, 247), 0.4)),
-        Box::new(PointLight::new(&V3::new(1.5, 2.0, -1.5), &Colour::from_f64(1.0, 1.0, 0.0), 100.0))
+    let block = [
+        (V3::new(2.0, 1.0, 0.0), &red), (V3::new(2.0, 1.0, 4.0), &red), (V3::new(2.0, 1.0, 2.0), &blue),
+        (V3::new(-2.0, 1.0, 4.0), &blue), (V3::new(0.0, 1.0, 2.0), &green), (V3::new(-2.0, 1.0, 0.0), &green),
+        (V3::new(0.0, 1.0, 0.0), &teal), (V3::new(0.0, 1.0, 4.0), &teal), (V3::new(-2.0, 1.0, 2.0), &mirror),
     ];
+    let offsets = [(V3::ZERO, None), (V3::new(0.0, 0.0, 6.0), Some(4)), (V3::new(6.0, 0.0, 0.0), Some(2)), (V3::new(6.0, 0.0, 6.0), Some(0))];
+    for (n, (offset, white_index)) in offsets.iter().enumerate() {
+        let mut group = SceneNode::group(&format!("Sphere block {}", n + 1)).with_transform(Transform::translation(offset));
+        for (i, (position, sphere)) in block.iter().enumerate() {
+            let sphere = if *white_index == Some(i) { &white } else { *sphere };
+            group.add_child(SceneNode::object(&format!("Sphere {}", i + 1), sphere.clone()).with_transform(Transform::translation(position)));
+        }
+        scene.add_child(group);
+    }
+
+    // The sun is its own light below, so it's left out of the sky
+    let sky = Arc::new(PreethamSky::new(&V3::new(-1.0, 1.0, -1.0), 3.0).with_sun_disc(false));
+
+    let scene = scene
+        // Big Sphere, bobbing up and down
+        .with_child(SceneNode::object("Big sphere", Arc::new(AnimatedObject::new(
+            Box::new(Sphere::new(V3::new(3.0, 3.0, 5.0), 2.0, Colour::from_u8(44, 90, 100), SurfaceType::new(0.0, 0.0, 0.7, 1.52, false, false))),
+            &V3::new(3.0, 3.0, 5.0),
+            Track::new(Interpolation::Spline).with_keyframe(0.0, V3::ZERO).with_keyframe(2.0, V3::UP).with_keyframe(4.0, V3::ZERO),
+            Track::new(Interpolation::Linear),
+        ))))
+        // Bowl - a white sphere with a red one cut out of its top
+        .with_child(SceneNode::object("Bowl", Arc::new(Csg::difference(
+            Box::new(Sphere::new(V3::new(4.0, 1.0, -3.0), 1.0, Colour::WHITE, diffuse)),
+            Box::new(Sphere::new(V3::new(4.0, 1.6, -3.0), 0.8, Colour::RED, diffuse)),
+        ).unwrap())))
+        .with_child(SceneNode::object("Light sphere", Arc::new(Sphere::new(V3::new(1.5, 2.0, -1.5), 0.2, Colour::BLACK, SurfaceType::new_emissive(&Colour::from_f64(1.0, 1.0, 0.0), 200.0)))))
+        .with_child(SceneNode::group("Walls")
+            .with_child(SceneNode::object("Red wall", Arc::new(Plane::new(&V3::new(10.0, 2.0, 4.0), &V3::new(0.0, 1.0, 0.0), &V3::new(0.0, 0.0, 1.0), Some((0.0, 0.0, 4.0, 4.0)), 
+                Box::new(SolidColour { colour: Colour::RED }),
+                SurfaceType::new(1.0, 0.0, 0.0, 1.54, true, false)
+            ))))
+            .with_child(SceneNode::object("White wall", Arc::new(Plane::new(&V3::new(6.0, 6.0, 8.0), &V3::new(0.0, 1.0, 0.0), &V3::new(-1.0, 0.0, 0.0), Some((0.0, 0.0, 4.0, 4.0)), 
+                Box::new(SolidColour { colour: Colour::WHITE }),
+                SurfaceType::new(1.0, 0.0, 0.0, 1.54, true, false)
+            )))))
+        .with_child(SceneNode::group("Lights")
+            .with_child(SceneNode::light("Sun", Arc::new(sky.get_sun(4.7))))
+            // Dims and brightens again over an animation
+            .with_child(SceneNode::light("Panel", Arc::new(AnimatedLight::new(
+                Box::new(RectLight::new(&V3::new(4.0, 4.0, 4.0), &V3::new(1.0, 0.0, 0.0), &V3::new(0.0, 0.0, 1.0), &Colour::WHITE, 470.0)),
+                Track::new(Interpolation::Linear).with_keyframe(0.0, 1.0).with_keyframe(2.0, 0.2).with_keyframe(4.0, 1.0),
+            ))))
+            .with_child(SceneNode::light("Spot", Arc::new(SpotLight::new(&V3::new(-2.0, 4.0, 8.0), &V3::new(0.5, -1.0, 0.0), 0.3, 0.5, &Colour::from_u8(255, 220, 180), 950.0)
+                .with_gobo(Box::new(Texture::new("static/textures/check.png").unwrap()))))));
 
     let render_config: RenderConfig = RenderConfig { 
         resolution: _240P, 
@@ -116,18 +118,92 @@ fn main() {
         sky_height: 1000.0, 
         sky_scale: 5000.0, 
         sky_texture: Box::new(Texture::new("static/textures/sky_prototype.png").unwrap()),
+        environment: Some(sky), // Some(Arc::new(EquirectangularMap::new("static/textures/sky.hdr").unwrap())),
         global_light: Colour::BLACK, // Colour::from_f64(0.2, 0.2, 0.2),
-        scene_objects,
-        scene_lights,
+        scene_objects: Vec::new(), // Filled in from 'scene'
+        scene_lights: Vec::new(),
         enable_full_bright: false,
         screenshot_enable_full_bright: false,
         enable_direct_lighting: true,
         screenshot_enable_direct_lighting: true,
         indirect_lighting_ray_count: 0, 
-        screenshot_indirect_lighting_ray_count: 500,
+        screenshot_indirect_lighting_ray_count: 16,
+        shadow_samples: 1,
+        screenshot_shadow_samples: 16,
+        light_sampling: LightSampling::MultipleImportance,
+        light_selection: LightSelection::All,
+        light_sampler: OnceLock::new(),
+        pixel_samples: 1,
+        screenshot_pixel_samples: 64,
+        filter: Filter::Mitchell(2.0, 1.0 / 3.0, 1.0 / 3.0),
+        sampler: SamplerType::Sobol,
+        seed: 0,
+        adaptive_threshold: Some(0.005),
+        adaptive_min_samples: 8,
+        save_sample_heatmap: false,
+        denoise: false,
+        screenshot_denoise: true,
+        denoiser: Denoiser::new(4),
+        checkpoint_interval: Some(60.0),
+    };
+
+    let camera = Camera::look_at(&V3::new(-5.0, 1.0, 0.0), &V3::new(0.0, 1.0, 0.0), &V3::UP, 98.0);
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // '--scene <path>' replaces the scene above with one from a file
+    let scene_path = args.iter().position(|a| a == "--scene").and_then(|i| args.get(i + 1)).cloned();
+    let scene = match &scene_path {
+        None => scene,
+        Some(path) => match scene_file::load(path) {
+            Ok(scene) => scene,
+            Err(e) => { println!("{}", e); return; }
+        }
     };
 
-    let camera = Camera::new(&V3::new(-5.0, 1.0, 0.0), (0.0, 0.0), 0.866);
+    // '--samples <n>' overrides the screenshot samples per pixel
+    let mut render_config = render_config;
+    let samples = args.iter().position(|a| a == "--samples").and_then(|i| args.get(i + 1)).and_then(|a| a.parse().ok());
+    if let Some(samples) = samples { render_config.screenshot_pixel_samples = samples; }
+
+    // '--animate <start> <end> <frame rate>' renders frames instead of opening the viewer
+    if let Some(i) = args.iter().position(|a| a == "--animate") {
+        let get_arg = |offset: usize, default: f64| args.get(i + offset).and_then(|a| a.parse().ok()).unwrap_or(default);
+        let (start, end, frame_rate) = (get_arg(1, 0.0), get_arg(2, 4.0), get_arg(3, 24.0));
+
+        // Swing round the scene towards the big sphere
+        let mut camera_track = CameraTrack::new();
+        camera_track.add_keyframe(0.0, &camera);
+        camera_track.add_keyframe(2.0, &Camera::look_at(&V3::new(-3.0, 2.0, -4.0), &V3::new(1.0, 1.5, 2.0), &V3::UP, 80.0));
+        camera_track.add_keyframe(4.0, &Camera::look_at(&V3::new(0.0, 3.0, -5.0), &V3::new(3.0, 3.0, 5.0), &V3::UP, 70.0));
+
+        // Shutter open for half of each frame
+        let camera = camera.with_shutter(0.0, 0.5 / frame_rate);
+
+        render_config.set_scene(&scene);
+        rendering::render_animation(&camera, &camera_track, &mut render_config, start, end, frame_rate, &CancellationToken::new());
+        return;
+    }
+
+    // '--resume <checkpoint>' finishes a cancelled screenshot from the camera it was taken with, instead of opening the viewer
+    // The checkpoint must come from the same scene and settings, though '--samples' can raise its sample count
+    if let Some(path) = args.iter().position(|a| a == "--resume").and_then(|i| args.get(i + 1)) {
+        render_config.set_scene(&scene);
+
+        let header = match CheckpointHeader::load(path) {
+            Ok(header) => header,
+            Err(e) => { println!("{}", e); return; }
+        };
+        let camera = header.camera;
+        if samples.is_none() { render_config.screenshot_pixel_samples = header.sample_target; }
+
+        let target = RenderTarget::new(render_config.screenshot_resolution);
+        if let Err(e) = target.load_checkpoint(path, &CheckpointHeader::new(&camera, &render_config)) { println!("{}", e); return; }
+        println!("Resuming from '{}' at {} samples per pixel", path, render_config.screenshot_pixel_samples);
+
+        rendering::take_screenshot(&camera, &render_config, &target, &mut |_| {}, &CancellationToken::new());
+        return;
+    }
 
-    Interface::start(render_config, camera);
+    Interface::start(render_config, scene, scene_path, camera);
 }
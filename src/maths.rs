@@ -1,5 +1,9 @@
 pub mod vectors;
 pub mod lines;
 pub mod intersection;
+pub mod distribution;
+pub mod quaternion;
+pub mod transform;
+pub mod sampler;
 
 pub use intersection::Intersection;
\ No newline at end of file
@@ -0,0 +1,94 @@
+// Piecewise constant distribution for importance sampling discrete weights
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+#[allow(dead_code)]
+impl Distribution1D {
+    pub fn new(weights: &[f64]) -> Self {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        let mut total = 0.0;
+        cdf.push(0.0);
+        for w in weights {
+            total += w.max(0.0);
+            cdf.push(total);
+        }
+
+        // Fall back to uniform if there is nothing to weight by
+        if total <= 0.0 {
+            for (i, c) in cdf.iter_mut().enumerate() { *c = i as f64; }
+            total = weights.len() as f64;
+        }
+
+        Self { cdf, total }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cdf.len() - 1
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    // Returns (index, probability of index, position within the index's bucket)
+    pub fn sample(&self, u: f64) -> (usize, f64, f64) {
+        let target = u * self.total;
+
+        let mut low = 0;
+        let mut high = self.len();
+        while low + 1 < high {
+            let mid = (low + high) / 2;
+            if self.cdf[mid] <= target { low = mid; } else { high = mid; }
+        }
+
+        let width = self.cdf[low + 1] - self.cdf[low];
+        let offset = if width > 0.0 { ((target - self.cdf[low]) / width).clamp(0.0, 1.0 - f64::EPSILON) } else { 0.5 };
+
+        (low, width / self.total, offset)
+    }
+
+    pub fn get_probability(&self, index: usize) -> f64 {
+        if index >= self.len() { return 0.0; }
+        (self.cdf[index + 1] - self.cdf[index]) / self.total
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+#[allow(dead_code)]
+impl Distribution2D {
+    // 'weights' is row-major with 'width' entries per row
+    pub fn new(weights: &[f64], width: usize) -> Self {
+        let rows: Vec<Distribution1D> = weights.chunks(width).map(Distribution1D::new).collect();
+        let row_totals: Vec<f64> = weights.chunks(width).map(|r| r.iter().map(|w| w.max(0.0)).sum()).collect();
+        Self { marginal: Distribution1D::new(&row_totals), rows }
+    }
+
+    // Returns ((x, y) continuous in [0, 1), probability density over [0, 1)^2)
+    pub fn sample(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        let (y, p_y, offset_y) = self.marginal.sample(u.1);
+        let (x, p_x, offset_x) = self.rows[y].sample(u.0);
+
+        let width = self.rows[y].len() as f64;
+        let height = self.rows.len() as f64;
+        (((x as f64 + offset_x) / width, (y as f64 + offset_y) / height), p_x * p_y * width * height)
+    }
+
+    pub fn get_pdf(&self, position: (f64, f64)) -> f64 {
+        let height = self.rows.len();
+        let y = ((position.1 * height as f64) as usize).min(height - 1);
+        let width = self.rows[y].len();
+        let x = ((position.0 * width as f64) as usize).min(width - 1);
+
+        self.marginal.get_probability(y) * self.rows[y].get_probability(x) * (width * height) as f64
+    }
+}
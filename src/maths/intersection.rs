@@ -5,13 +5,14 @@ use super::lines::{Line, SizedLine};
 #[derive(Debug, Clone, Copy)]
 pub struct Intersection {
     pub sized_line: SizedLine,
-    pub position: V3
+    pub position: V3,
+    pub part: u32, // Which part of a composite object was hit, for it to pick out again without recalculating, 0 for simple objects
 }
 
 #[allow(dead_code)]
 impl Intersection {
     pub fn new(line: &Line, scale: f64, position: &V3) -> Self {
-        Self { sized_line: SizedLine::from_line(line, scale), position: position.clone() }
+        Self { sized_line: SizedLine::from_line(line, scale), position: position.clone(), part: 0 }
     }
 
     pub fn closest_bounded(hits: &Vec<Intersection>, near_scale: f64, far_scale: f64) -> Option<&Intersection> {
@@ -4,7 +4,8 @@ use super::vectors::V3;
 #[derive(Debug, Clone, Copy)]
 pub struct Line {
     pub point: V3,
-    pub vector: V3
+    pub vector: V3,
+    pub time: f64, // Seconds after the frame's time, for motion blur
 }
 
 #[allow(dead_code)]
@@ -12,10 +13,16 @@ impl Line {
     pub fn new(point: &V3, vector: &V3) -> Self {
         Self {
             point: point.clone(),
-            vector: vector.clone()
+            vector: vector.clone(),
+            time: 0.0
         }
     }
 
+    pub fn at_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
     pub fn scale(&self, scale: f64) -> V3 {
         return self.point + (self.vector * scale);
     }
@@ -0,0 +1,155 @@
+use std::ops;
+
+use super::vectors::V3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+#[allow(dead_code)]
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn from_axis_angle(axis: &V3, radians: f64) -> Self {
+        let axis = axis.normalised();
+        let s = (radians / 2.0).sin();
+        Self::new((radians / 2.0).cos(), axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    // Rotation taking FORWARD, UP and RIGHT onto the given orthonormal basis
+    pub fn from_basis(forward: &V3, up: &V3, right: &V3) -> Self {
+        let (m00, m01, m02) = (forward.x, up.x, right.x);
+        let (m10, m11, m12) = (forward.y, up.y, right.y);
+        let (m20, m21, m22) = (forward.z, up.z, right.z);
+
+        let trace = m00 + m11 + m22;
+        let q;
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            q = Self::new(0.25 * s, (m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s);
+        }
+        else if m00 > m11 && m00 > m22 {
+            let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+            q = Self::new((m21 - m12) / s, 0.25 * s, (m01 + m10) / s, (m02 + m20) / s);
+        }
+        else if m11 > m22 {
+            let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+            q = Self::new((m02 - m20) / s, (m01 + m10) / s, 0.25 * s, (m12 + m21) / s);
+        }
+        else {
+            let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+            q = Self::new((m10 - m01) / s, (m02 + m20) / s, (m12 + m21) / s, 0.25 * s);
+        }
+
+        q.normalised()
+    }
+
+    // Rotation pointing FORWARD along 'forward' with UP as close to 'up' as possible
+    pub fn look_along(forward: &V3, up: &V3) -> Self {
+        let forward = forward.normalised();
+        let mut right = forward.cross(up);
+        if right.magnitude() < 1e-9 { right = forward.get_basis().0; }
+        let right = right.normalised();
+        let up = right.cross(&forward).normalised();
+
+        Self::from_basis(&forward, &up, &right)
+    }
+
+    pub fn magnitude(&self) -> f64 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn normalised(&self) -> Self {
+        let m = 1.0 / self.magnitude();
+        Self::new(self.w * m, self.x * m, self.y * m, self.z * m)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(&self, v: &V3) -> V3 {
+        let q = V3::new(self.x, self.y, self.z);
+        let t = q.cross(v) * 2.0;
+        v + (t * self.w) + q.cross(&t)
+    }
+
+    pub fn get_forward(&self) -> V3 { self.rotate(&V3::FORWARD) }
+    pub fn get_up(&self) -> V3 { self.rotate(&V3::UP) }
+    pub fn get_right(&self) -> V3 { self.rotate(&V3::RIGHT) }
+
+    #[allow(non_snake_case)]
+    pub const IDENTITY: Quaternion = Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+}
+
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::maths::vectors::V3;
+
+    use super::Quaternion;
+
+    fn assert_close(a: &V3, b: &V3) {
+        assert!((a - b).magnitude() < 1e-9, "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn axis_angle_rotations_follow_the_right_hand_rule() {
+        assert_close(&Quaternion::from_axis_angle(&V3::RIGHT, PI / 2.0).rotate(&V3::FORWARD), &V3::UP);
+        assert_close(&Quaternion::from_axis_angle(&V3::UP, PI / 2.0).rotate(&V3::FORWARD), &V3::LEFT);
+        assert_close(&Quaternion::from_axis_angle(&V3::FORWARD, PI / 2.0).rotate(&V3::UP), &V3::RIGHT);
+    }
+
+    #[test]
+    fn products_apply_the_right_rotation_first() {
+        let yaw = Quaternion::from_axis_angle(&V3::UP, 0.7);
+        let pitch = Quaternion::from_axis_angle(&V3::RIGHT, -0.4);
+        let v = V3::new(0.3, -1.2, 2.0);
+
+        assert_close(&(yaw * pitch).rotate(&v), &yaw.rotate(&pitch.rotate(&v)));
+        assert_close(&(yaw * yaw.conjugate()).rotate(&v), &v);
+    }
+
+    #[test]
+    fn basis_round_trips() {
+        let orientation = Quaternion::from_axis_angle(&V3::new(1.0, -2.0, 0.5), 2.3);
+        let rebuilt = Quaternion::from_basis(&orientation.get_forward(), &orientation.get_up(), &orientation.get_right());
+
+        for v in [V3::FORWARD, V3::UP, V3::RIGHT, V3::new(0.3, -1.2, 2.0)] {
+            assert_close(&rebuilt.rotate(&v), &orientation.rotate(&v));
+        }
+    }
+
+    #[test]
+    fn look_along_keeps_up_as_close_as_possible() {
+        let forward = V3::new(1.0, -0.5, 2.0);
+        let orientation = Quaternion::look_along(&forward, &V3::UP);
+
+        assert_close(&orientation.get_forward(), &forward.normalised());
+        assert!(orientation.get_right().y.abs() < 1e-9);
+        assert!(orientation.get_up().y > 0.0);
+
+        // Straight up has no horizon to keep level, but still has to point the right way
+        assert_close(&Quaternion::look_along(&V3::UP, &V3::UP).get_forward(), &V3::UP);
+    }
+}
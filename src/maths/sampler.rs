@@ -0,0 +1,216 @@
+use std::ops::Range;
+
+pub mod independent_sampler;
+pub mod stratified_sampler;
+pub mod halton_sampler;
+pub mod sobol_sampler;
+pub mod blue_noise_sampler;
+
+pub use independent_sampler::IndependentSampler;
+pub use stratified_sampler::StratifiedSampler;
+pub use halton_sampler::HaltonSampler;
+pub use sobol_sampler::SobolSampler;
+pub use blue_noise_sampler::BlueNoiseSampler;
+
+// Source of sample values in [0, 1), each call moves on to the next dimension of the current sample
+pub trait Sampler {
+    // Begin sample 'index' of 'pixel', returning to the first dimension
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+
+    fn get_range(&mut self, range: Range<f64>) -> f64 {
+        range.start + self.get_1d() * (range.end - range.start)
+    }
+
+    fn get_index(&mut self, count: u32) -> u32 {
+        ((self.get_1d() * count as f64) as u32).min(count.saturating_sub(1))
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+#[allow(dead_code)]
+impl SamplerType {
+    pub fn create(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerType::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerType::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerType::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+// Integer hash used to decorrelate pixels and dimensions
+pub fn hash(values: &[u32]) -> u32 {
+    let mut h: u32 = 0x9e3779b9;
+    for v in values {
+        h ^= v.wrapping_add(0x7f4a7c15).wrapping_add(h << 6).wrapping_add(h >> 2);
+        h = (h ^ (h >> 16)).wrapping_mul(0x21f0aaad);
+        h = (h ^ (h >> 15)).wrapping_mul(0x735a2d97);
+        h ^= h >> 15;
+    }
+    h
+}
+
+// Seed for a stream of pseudorandom values that depends only on the render seed and 'values'
+pub fn hash_seed(seed: u64, values: &[u32]) -> u64 {
+    let (low, high) = (seed as u32, (seed >> 32) as u32);
+    let mut all = vec![low, high];
+    all.extend_from_slice(values);
+    let first = hash(&all);
+    all.push(first);
+    ((first as u64) << 32) | hash(&all) as u64
+}
+
+// Random permutation of 0..count picked by 'seed' without storing it (Kensler, Correlated Multi-Jittered Sampling)
+pub fn permute(mut i: u32, count: u32, seed: u32) -> u32 {
+    if count <= 1 { return 0; }
+
+    let mut w = count - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < count { break; }
+    }
+
+    (i.wrapping_add(seed)) % count
+}
+
+pub fn to_unit(value: u32) -> f64 {
+    (value as f64 / 4294967296.0).min(1.0 - f64::EPSILON)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::SamplerType;
+
+    const TYPES: [SamplerType; 5] = [SamplerType::Independent, SamplerType::Stratified, SamplerType::Halton, SamplerType::Sobol, SamplerType::BlueNoise];
+
+    // The first 'count' samples of 'pixel', 'dimensions' values each
+    fn get_values(sampler_type: SamplerType, pixel: (u32, u32), count: u32, dimensions: u32) -> Vec<Vec<f64>> {
+        let mut sampler = sampler_type.create(count, 3);
+        (0..count).map(|i| {
+            sampler.start_sample(pixel, i);
+            (0..dimensions).map(|_| sampler.get_1d()).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn values_are_in_range_and_repeatable() {
+        for sampler_type in TYPES {
+            let values = get_values(sampler_type, (3, 5), 32, 8);
+
+            assert!(values.iter().flatten().all(|v| (0.0..1.0).contains(v)), "{:?}", sampler_type);
+            assert_eq!(values, get_values(sampler_type, (3, 5), 32, 8), "{:?}", sampler_type);
+            assert_ne!(values, get_values(sampler_type, (4, 5), 32, 8), "{:?}", sampler_type);
+        }
+    }
+
+    #[test]
+    fn stratified_samplers_fill_every_stratum() {
+        for sampler_type in [SamplerType::Stratified, SamplerType::Halton, SamplerType::Sobol] {
+            let mut sampler = sampler_type.create(16, 3);
+
+            // One sample in each 1/16th of the first dimension, and for Sobol and stratified each 1/4 x 1/4 cell of the next pair
+            let (mut bins, mut cells) = ([0; 16], [0; 16]);
+            for i in 0..16 {
+                sampler.start_sample((7, 2), i);
+                bins[(sampler.get_1d() * 16.0) as usize] += 1;
+                let (u, v) = sampler.get_2d();
+                cells[(u * 4.0) as usize + 4 * (v * 4.0) as usize] += 1;
+            }
+
+            assert_eq!(bins, [1; 16], "{:?}", sampler_type);
+            if sampler_type != SamplerType::Halton { assert_eq!(cells, [1; 16], "{:?}", sampler_type); }
+        }
+    }
+
+    // Average error estimating the integral of a smooth function over the square with 64 samples per pixel
+    fn get_integration_error(sampler_type: SamplerType) -> f64 {
+        let exact = (2.0 / PI) / 3.0;
+        let pixels = 64;
+
+        (0..pixels).map(|p| {
+            let mut sampler = sampler_type.create(64, 3);
+            let estimate = (0..64).map(|i| {
+                sampler.start_sample((p, 0), i);
+                let (x, y) = sampler.get_2d();
+                (PI * x).sin() * y * y
+            }).sum::<f64>() / 64.0;
+            (estimate - exact).abs()
+        }).sum::<f64>() / pixels as f64
+    }
+
+    #[test]
+    fn low_discrepancy_samplers_converge_faster_than_independent_ones() {
+        let independent = get_integration_error(SamplerType::Independent);
+
+        for sampler_type in [SamplerType::Stratified, SamplerType::Halton, SamplerType::Sobol, SamplerType::BlueNoise] {
+            let error = get_integration_error(sampler_type);
+            assert!(error < 0.5 * independent, "{:?}: {} vs {}", sampler_type, error, independent);
+        }
+    }
+
+    #[test]
+    fn blue_noise_spreads_neighbouring_pixels_apart() {
+        let first_values = |sampler_type: SamplerType| -> Vec<f64> {
+            let mut sampler = sampler_type.create(1, 3);
+            (0..64 * 64).map(|p| {
+                sampler.start_sample((p % 64, p / 64), 0);
+                sampler.get_1d()
+            }).collect()
+        };
+        let neighbour_difference = |values: &[f64]| -> f64 {
+            (0..64 * 64).filter(|p| p % 64 != 63).map(|p| (values[p] - values[p + 1]).abs()).sum::<f64>() / (63.0 * 64.0)
+        };
+
+        // Every value of the mask appears once in a tile, so the values are still uniform
+        let blue_noise = first_values(SamplerType::BlueNoise);
+        let mut bins = [0; 64];
+        for value in &blue_noise { bins[(value * 64.0) as usize] += 1; }
+        assert_eq!(bins, [64; 64]);
+
+        let (blue_difference, independent_difference) = (neighbour_difference(&blue_noise), neighbour_difference(&first_values(SamplerType::Independent)));
+        assert!(blue_difference > independent_difference + 0.05, "{} vs {}", blue_difference, independent_difference);
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::OnceLock;
+
+use super::{Sampler, hash};
+
+const MASK_SIZE: usize = 64;
+const GOLDEN_RATIO: f64 = 0.6180339887498949;
+const PLASTIC_RATIOS: (f64, f64) = (0.7548776662466927, 0.5698402909980532);
+
+static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+
+// Tiled blue noise mask, offset for every dimension and advanced by the golden ratio (or the R2 sequence in 2D) for every sample,
+// so neighbouring pixels get well separated values and any error shows up as high frequency noise
+pub struct BlueNoiseSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+#[allow(dead_code)]
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, pixel: (0, 0), index: 0, dimension: 0 }
+    }
+
+    // Ranks cells by farthest point ordering on the torus - each new cell is the one furthest from all
+    // previous cells, so any prefix of the ranking is evenly spread
+    fn build_mask() -> Vec<f64> {
+        let count = MASK_SIZE * MASK_SIZE;
+        let mut mask = vec![0.0; count];
+        let mut distances = vec![f64::INFINITY; count];
+        let mut placed = vec![false; count];
+
+        // Breaks ties between equally distant cells without favouring one corner
+        let jitter: Vec<f64> = (0..count).map(|i| (hash(&[i as u32]) as f64 / 4294967296.0) * 0.5).collect();
+
+        let mut next = (hash(&[MASK_SIZE as u32]) as usize) % count;
+        for rank in 0..count {
+            placed[next] = true;
+            mask[next] = (rank as f64 + 0.5) / count as f64;
+
+            let (nx, ny) = ((next % MASK_SIZE) as i32, (next / MASK_SIZE) as i32);
+            let mut furthest = (0, -1.0);
+            for i in 0..count {
+                if placed[i] { continue; }
+
+                let dx = ((i % MASK_SIZE) as i32 - nx).abs();
+                let dy = ((i / MASK_SIZE) as i32 - ny).abs();
+                let dx = dx.min(MASK_SIZE as i32 - dx) as f64;
+                let dy = dy.min(MASK_SIZE as i32 - dy) as f64;
+                distances[i] = distances[i].min(dx * dx + dy * dy);
+
+                let score = distances[i] + jitter[i];
+                if score > furthest.1 { furthest = (i, score); }
+            }
+            next = furthest.0;
+        }
+
+        mask
+    }
+
+    fn get_mask() -> &'static Vec<f64> {
+        MASK.get_or_init(Self::build_mask)
+    }
+
+    fn get_mask_value(&mut self) -> f64 {
+        let offset = hash(&[self.seed as u32, (self.seed >> 32) as u32, self.dimension]);
+        let x = (self.pixel.0 as usize + (offset as usize % MASK_SIZE)) % MASK_SIZE;
+        let y = (self.pixel.1 as usize + ((offset >> 16) as usize % MASK_SIZE)) % MASK_SIZE;
+        self.dimension += 1;
+
+        Self::get_mask()[y * MASK_SIZE + x]
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        (self.get_mask_value() + self.index as f64 * GOLDEN_RATIO).fract()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (
+            (self.get_mask_value() + self.index as f64 * PLASTIC_RATIOS.0).fract(),
+            (self.get_mask_value() + self.index as f64 * PLASTIC_RATIOS.1).fract(),
+        )
+    }
+}
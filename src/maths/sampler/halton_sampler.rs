@@ -0,0 +1,50 @@
+use super::{Sampler, hash, to_unit};
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+// Radical inverse in a different prime base per dimension, randomly rotated per pixel
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+#[allow(dead_code)]
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, pixel: (0, 0), index: 0, dimension: 0 }
+    }
+
+    pub fn radical_inverse(base: u32, mut index: u32) -> f64 {
+        let inverse_base = 1.0 / base as f64;
+        let mut factor = inverse_base;
+        let mut result = 0.0;
+        while index > 0 {
+            result += (index % base) as f64 * factor;
+            index /= base;
+            factor *= inverse_base;
+        }
+        result
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        // Dimensions past the prime table reuse the bases with a different rotation
+        let base = PRIMES[(self.dimension as usize) % PRIMES.len()];
+        let rotation = to_unit(hash(&[self.seed as u32, (self.seed >> 32) as u32, self.pixel.0, self.pixel.1, self.dimension]));
+        self.dimension += 1;
+
+        (Self::radical_inverse(base, self.index) + rotation).fract()
+    }
+}
@@ -0,0 +1,26 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use super::{Sampler, hash_seed};
+
+// Uncorrelated pseudorandom values, reseeded for every sample so results do not depend on the order pixels are rendered in
+pub struct IndependentSampler {
+    rng: StdRng,
+    seed: u64,
+}
+
+#[allow(dead_code)]
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), seed }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.rng = StdRng::seed_from_u64(hash_seed(self.seed, &[pixel.0, pixel.1, index]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+}
@@ -0,0 +1,71 @@
+use super::{Sampler, hash, to_unit};
+
+// First two Sobol dimensions padded to higher dimensions, with hash based Owen scrambling and
+// an independent shuffle of the sample order for each pair of dimensions (Burley, Practical Hash-based Owen Scrambling)
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+#[allow(dead_code)]
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, pixel: (0, 0), index: 0, dimension: 0 }
+    }
+
+    fn sobol(index: u32, dimension: u32) -> u32 {
+        if dimension == 0 { return index.reverse_bits(); }
+
+        // Second dimension, generated by the primitive polynomial x + 1
+        let mut result = 0;
+        let mut v: u32 = 1 << 31;
+        let mut i = index;
+        while i != 0 {
+            if i & 1 != 0 { result ^= v; }
+            i >>= 1;
+            v ^= v >> 1;
+        }
+        result
+    }
+
+    fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+        x = x.wrapping_add(seed);
+        x ^= x.wrapping_mul(0x6c50b47c);
+        x ^= x.wrapping_mul(0xb82f1e52);
+        x ^= x.wrapping_mul(0xc7afe638);
+        x ^= x.wrapping_mul(0x8d22f6e6);
+        x
+    }
+
+    fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+        Self::laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+    }
+
+    fn get_scrambled(&self, dimension: u32, pair: u32) -> f64 {
+        let seed = hash(&[self.seed as u32, (self.seed >> 32) as u32, self.pixel.0, self.pixel.1, pair]);
+        let index = Self::nested_uniform_scramble(self.index, seed);
+        to_unit(Self::nested_uniform_scramble(Self::sobol(index, dimension), hash(&[seed, dimension])))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let value = self.get_scrambled(0, self.dimension);
+        self.dimension += 1;
+        value
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let value = (self.get_scrambled(0, self.dimension), self.get_scrambled(1, self.dimension));
+        self.dimension += 2;
+        value
+    }
+}
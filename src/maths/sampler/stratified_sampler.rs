@@ -0,0 +1,56 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use super::{Sampler, hash, hash_seed, permute};
+
+// Jittered strata, shuffled independently for every dimension of every pixel so dimensions stay uncorrelated
+pub struct StratifiedSampler {
+    rng: StdRng,
+    seed: u64,
+    samples_per_pixel: u32,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+#[allow(dead_code)]
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed), seed, samples_per_pixel: samples_per_pixel.max(1), pixel: (0, 0), index: 0, dimension: 0 }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.rng = StdRng::seed_from_u64(hash_seed(self.seed, &[pixel.0, pixel.1, index]));
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seed = hash(&[self.seed as u32, (self.seed >> 32) as u32, self.pixel.0, self.pixel.1, self.dimension]);
+        let stratum = permute(self.index % self.samples_per_pixel, self.samples_per_pixel, seed);
+        self.dimension += 1;
+
+        (stratum as f64 + self.rng.gen::<f64>()) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // Grid of strata over the square, as close to square as the sample count allows
+        let columns = (self.samples_per_pixel as f64).sqrt() as u32;
+        let rows = self.samples_per_pixel / columns;
+        let count = columns * rows;
+
+        let seed = hash(&[self.seed as u32, (self.seed >> 32) as u32, self.pixel.0, self.pixel.1, self.dimension]);
+        let stratum = permute(self.index % self.samples_per_pixel, self.samples_per_pixel, seed);
+        self.dimension += 2;
+
+        // Samples beyond the full grid fall back to plain jitter
+        if stratum >= count { return (self.rng.gen(), self.rng.gen()); }
+
+        (
+            ((stratum % columns) as f64 + self.rng.gen::<f64>()) / columns as f64,
+            ((stratum / columns) as f64 + self.rng.gen::<f64>()) / rows as f64,
+        )
+    }
+}
@@ -0,0 +1,257 @@
+use std::ops;
+
+use super::{vectors::V3, quaternion::Quaternion};
+
+// 4x4 matrix acting on column vectors, kept together with its inverse so both directions are cheap
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub matrix: [[f64; 4]; 4],
+    pub inverse: [[f64; 4]; 4],
+}
+
+#[allow(dead_code)]
+impl Transform {
+    // None if 'matrix' can't be inverted
+    pub fn new(matrix: [[f64; 4]; 4]) -> Option<Self> {
+        Some(Self { matrix, inverse: Self::invert(&matrix)? })
+    }
+
+    pub fn translation(offset: &V3) -> Self {
+        let mut transform = Self::IDENTITY;
+        (transform.matrix[0][3], transform.matrix[1][3], transform.matrix[2][3]) = (offset.x, offset.y, offset.z);
+        (transform.inverse[0][3], transform.inverse[1][3], transform.inverse[2][3]) = (-offset.x, -offset.y, -offset.z);
+        transform
+    }
+
+    // Components must be non-zero
+    pub fn scale(scale: &V3) -> Self {
+        let mut transform = Self::IDENTITY;
+        (transform.matrix[0][0], transform.matrix[1][1], transform.matrix[2][2]) = (scale.x, scale.y, scale.z);
+        (transform.inverse[0][0], transform.inverse[1][1], transform.inverse[2][2]) = (1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z);
+        transform
+    }
+
+    pub fn rotation(rotation: &Quaternion) -> Self {
+        let rotation = rotation.normalised();
+        let columns = [rotation.get_forward(), rotation.get_up(), rotation.get_right()];
+
+        // Orthonormal, so the inverse is the transpose
+        let mut transform = Self::IDENTITY;
+        for (j, column) in columns.iter().enumerate() {
+            for (i, value) in [column.x, column.y, column.z].iter().enumerate() {
+                transform.matrix[i][j] = *value;
+                transform.inverse[j][i] = *value;
+            }
+        }
+        transform
+    }
+
+    // Scale, then rotate, then translate
+    pub fn from_parts(translation: &V3, rotation: &Quaternion, scale: &V3) -> Self {
+        Self::translation(translation) * Self::rotation(rotation) * Self::scale(scale)
+    }
+
+    // Exact, as products of identities stay exactly the identity
+    pub fn is_identity(&self) -> bool {
+        self.matrix == Self::IDENTITY.matrix
+    }
+
+    pub fn inverted(&self) -> Self {
+        Self { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    pub fn transform_point(&self, point: &V3) -> V3 {
+        Self::apply(&self.matrix, point, 1.0)
+    }
+
+    pub fn transform_vector(&self, vector: &V3) -> V3 {
+        Self::apply(&self.matrix, vector, 0.0)
+    }
+
+    // Normals need the inverse transpose to stay perpendicular to surfaces under non-uniform scale
+    pub fn transform_normal(&self, normal: &V3) -> V3 {
+        let m = &self.inverse;
+        V3::new(
+            m[0][0] * normal.x + m[1][0] * normal.y + m[2][0] * normal.z,
+            m[0][1] * normal.x + m[1][1] * normal.y + m[2][1] * normal.z,
+            m[0][2] * normal.x + m[1][2] * normal.y + m[2][2] * normal.z,
+        )
+    }
+
+    pub fn inverse_transform_point(&self, point: &V3) -> V3 {
+        Self::apply(&self.inverse, point, 1.0)
+    }
+
+    pub fn inverse_transform_vector(&self, vector: &V3) -> V3 {
+        Self::apply(&self.inverse, vector, 0.0)
+    }
+
+    // Axis aligned box holding the transformed corners of 'bounds'
+    pub fn transform_bounds(&self, bounds: &(V3, V3)) -> (V3, V3) {
+        let (min, max) = bounds;
+
+        let mut result = (V3::ONE * f64::INFINITY, V3::ONE * f64::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = V3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            let corner = self.transform_point(&corner);
+            result = (result.0.min(&corner), result.1.max(&corner));
+        }
+        result
+    }
+
+    // How much small areas of a surface with 'normal' are scaled by, which varies with the normal under non-uniform scale
+    // A patch's area times its unit normal transforms like a normal scaled by the determinant
+    pub fn get_area_scale(&self, normal: &V3) -> f64 {
+        self.get_determinant().abs() * self.transform_normal(&normal.normalised()).magnitude()
+    }
+
+    // The scale if every direction is stretched equally, e.g. any mix of moves, turns and uniform scales
+    pub fn get_uniform_scale(&self) -> Option<f64> {
+        let m = &self.matrix;
+        let columns = [0, 1, 2].map(|j| V3::new(m[0][j], m[1][j], m[2][j]));
+        let scale = columns[0].magnitude();
+
+        let tolerance = 1e-9 * scale * scale;
+        let orthogonal = [(0, 1), (0, 2), (1, 2)].iter().all(|(a, b)| columns[*a].dot(&columns[*b]).abs() <= tolerance);
+        let equal = columns.iter().all(|c| (c.dot(c) - scale * scale).abs() <= tolerance);
+        if orthogonal && equal { Some(scale) } else { None }
+    }
+
+    // Of the upper 3x3, how much volumes are scaled by
+    pub fn get_determinant(&self) -> f64 {
+        let m = &self.matrix;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    fn apply(m: &[[f64; 4]; 4], v: &V3, w: f64) -> V3 {
+        let result = V3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3] * w,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3] * w,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * w,
+        );
+        if w == 0.0 { return result; }
+
+        // Projective matrices need the divide, affine ones have 1 here
+        let h = m[3][0] * v.x + m[3][1] * v.y + m[3][2] * v.z + m[3][3] * w;
+        if h == 1.0 || h == 0.0 { result } else { result / h }
+    }
+
+    fn multiply(a: &[[f64; 4]; 4], b: &[[f64; 4]; 4]) -> [[f64; 4]; 4] {
+        let mut result = [[0.0; 4]; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                result[i][j] = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+            }
+        }
+        result
+    }
+
+    // Gauss-Jordan elimination with partial pivoting
+    fn invert(matrix: &[[f64; 4]; 4]) -> Option<[[f64; 4]; 4]> {
+        let mut m = *matrix;
+        let mut inverse = Self::IDENTITY.matrix;
+
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|a, b| m[*a][column].abs().partial_cmp(&m[*b][column].abs()).unwrap())?;
+            if m[pivot][column].abs() < 1e-12 { return None; }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for j in 0..4 {
+                m[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column { continue; }
+                let factor = m[row][column];
+                for j in 0..4 {
+                    m[row][j] -= factor * m[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+
+        Some(inverse)
+    }
+
+    #[allow(non_snake_case)]
+    pub const IDENTITY: Transform = Transform {
+        matrix: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+        inverse: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+    };
+}
+
+// 'self * rhs' applies 'rhs' first
+impl ops::Mul<Transform> for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            matrix: Self::multiply(&self.matrix, &rhs.matrix),
+            inverse: Self::multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::maths::{vectors::V3, quaternion::Quaternion};
+
+    use super::Transform;
+
+    #[test]
+    fn bounds_hold_every_transformed_corner() {
+        let transform = Transform::translation(&V3::new(0.0, 0.0, 5.0)) * Transform::rotation(&Quaternion::from_axis_angle(&V3::UP, PI / 4.0));
+        let (min, max) = transform.transform_bounds(&(V3::ONE * -1.0, V3::ONE));
+
+        let extent = 2.0f64.sqrt();
+        assert!((min - V3::new(-extent, -1.0, 5.0 - extent)).magnitude() < 1e-9, "{:?}", min);
+        assert!((max - V3::new(extent, 1.0, 5.0 + extent)).magnitude() < 1e-9, "{:?}", max);
+    }
+
+    #[test]
+    fn area_scale_depends_on_the_surface_direction() {
+        let transform = Transform::rotation(&Quaternion::from_axis_angle(&V3::UP, 1.0)) * Transform::scale(&V3::new(2.0, 3.0, 4.0));
+
+        // A patch facing along x is stretched by the y and z scales, and so on
+        for (normal, expected) in [(V3::FORWARD, 12.0), (V3::UP, 8.0), (V3::RIGHT, 6.0)] {
+            assert!((transform.get_area_scale(&normal) - expected).abs() < 1e-9, "{:?}", normal);
+        }
+        assert!((Transform::scale(&(V3::ONE * 3.0)).get_area_scale(&V3::new(1.0, 2.0, 3.0)) - 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn uniform_scale_survives_moves_and_turns() {
+        let turn = Transform::rotation(&Quaternion::from_axis_angle(&V3::new(1.0, 2.0, 3.0), 0.7));
+        let uniform = Transform::translation(&V3::new(1.0, 2.0, 3.0)) * turn * Transform::scale(&(V3::ONE * 2.5));
+        assert!((uniform.get_uniform_scale().unwrap() - 2.5).abs() < 1e-9);
+
+        assert!((turn * Transform::scale(&V3::new(2.0, 2.0, 3.0))).get_uniform_scale().is_none());
+    }
+
+    #[test]
+    fn products_apply_the_right_hand_side_first() {
+        let transform = Transform::translation(&V3::new(0.0, 0.0, 5.0)) * Transform::rotation(&Quaternion::from_axis_angle(&V3::UP, PI / 2.0)) * Transform::scale(&V3::new(2.0, 1.0, 1.0));
+
+        // Stretched along x, turned onto z, then moved
+        let point = transform.transform_point(&V3::FORWARD);
+        assert!((point - V3::new(0.0, 0.0, 3.0)).magnitude() < 1e-9, "{:?}", point);
+        assert!((transform.inverse_transform_point(&point) - V3::FORWARD).magnitude() < 1e-9);
+        assert!((transform.transform_vector(&V3::FORWARD) - V3::LEFT * 2.0).magnitude() < 1e-9);
+
+        // Inverting the matrix from scratch finds the same inverse
+        let rebuilt = Transform::new(transform.matrix).unwrap();
+        assert!((rebuilt.inverse_transform_point(&point) - V3::FORWARD).magnitude() < 1e-9);
+        assert!(Transform::new([[0.0; 4]; 4]).is_none());
+    }
+}
@@ -1,7 +1,6 @@
 use std::f64::consts::PI;
 use std::ops;
-use rand::Rng;
-use rand::rngs::ThreadRng;
+use super::sampler::Sampler;
 
 #[derive(Debug, Clone, Copy)]
 pub struct V3 {
@@ -69,28 +68,64 @@ impl V3 {
         + about
     }
 
+    pub fn min(&self, rhs: &V3) -> V3 {
+        V3::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    pub fn max(&self, rhs: &V3) -> V3 {
+        V3::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+
     pub fn reflected(&self, normal: &V3) -> V3 {
         let n = normal.normalised();
         self.clone() - (n * (2.0 * (self.dot(&n))))
     }
 
-    pub fn get_random(direction: &V3, spread: f64, rng: &mut ThreadRng) -> V3 {
-        let b3 = direction.normalised();
-        let different;
-        if b3.x < 0.5 { different = V3::new(1.0, 0.0, 0.0); }
-        else { different = V3::new(0.0, 1.0, 0.0); }
+    // Snell's law, for 'normal' facing against this direction and 'eta' the ratio of refractive indices it leaves and enters
+    // None for total internal reflection
+    pub fn refracted(&self, normal: &V3, eta: f64) -> Option<V3> {
+        let (d, n) = (self.normalised(), normal.normalised());
+        let cos_i = -d.dot(&n);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 { return None; }
+
+        Some((d * eta) + (n * (eta * cos_i - (1.0 - sin2_t).sqrt())))
+    }
+
+    pub fn get_basis(&self) -> (V3, V3) {
+        let b3 = self.normalised();
+        let different = if b3.x.abs() < 0.5 { V3::new(1.0, 0.0, 0.0) } else { V3::new(0.0, 1.0, 0.0) };
         let b1 = b3.cross(&different).normalised();
         let b2 = b1.cross(&b3).normalised();
+        (b1, b2)
+    }
+
+    pub fn get_random(direction: &V3, spread: f64, rng: &mut dyn Sampler) -> V3 {
+        let b3 = direction.normalised();
+        let (b1, b2) = b3.get_basis();
 
-        let z = rng.gen_range(spread.cos()..1.0);
+        let (u, v) = rng.get_2d();
+        let z = spread.cos() + u * (1.0 - spread.cos());
         let r = (1.0 - (z * z)).sqrt();
-        let theta = rng.gen_range(-PI..PI);
+        let theta = (2.0 * v - 1.0) * PI;
         let x = r * theta.cos();
         let y = r * theta.sin();
 
         (b1 * x) + (b2 * y) + (b3 * z)
     }
 
+    // Cosine weighted direction in the hemisphere around 'normal' - pdf is cos(theta) / PI
+    pub fn get_random_cosine(normal: &V3, rng: &mut dyn Sampler) -> V3 {
+        let b3 = normal.normalised();
+        let (b1, b2) = b3.get_basis();
+
+        let (u, v) = rng.get_2d();
+        let r = u.sqrt();
+        let theta = (2.0 * v - 1.0) * PI;
+
+        (b1 * (r * theta.cos())) + (b2 * (r * theta.sin())) + (b3 * (1.0 - u).sqrt())
+    }
+
     #[allow(non_snake_case)]
     pub const ZERO: V3 = V3 { x: 0.0, y: 0.0, z: 0.0};
     #[allow(non_snake_case)]
@@ -277,3 +312,23 @@ impl PartialEq<V3> for V3 {
 }
 
 impl Eq for V3 {}
+
+#[cfg(test)]
+mod tests {
+    use crate::maths::sampler::{Sampler, IndependentSampler};
+
+    use super::V3;
+
+    #[test]
+    fn random_directions_stay_in_the_cone_for_any_axis() {
+        let mut rng = IndependentSampler::new(0);
+        for direction in [V3::FORWARD, V3::BACK, V3::UP, V3::DOWN, V3::new(-1.0, 1e-9, 0.0), V3::new(0.6, -0.8, 0.0)] {
+            for i in 0..100 {
+                rng.start_sample((0, 0), i);
+                let random = V3::get_random(&direction, 0.3, &mut rng);
+                assert!((random.magnitude() - 1.0).abs() < 1e-9, "{:?}: {:?}", direction, random);
+                assert!(random.angle_to(&direction) <= 0.3 + 1e-9, "{:?}: {:?}", direction, random);
+            }
+        }
+    }
+}
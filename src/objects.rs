@@ -1,7 +1,18 @@
 pub mod plane;
 pub mod sphere;
+pub mod animated_object;
+pub mod instance;
+pub mod csg;
 pub use plane::Plane;
 pub use sphere::Sphere;
+pub use animated_object::AnimatedObject;
+pub use instance::Instance;
+#[allow(unused_imports)]
+pub use csg::{Csg, CsgOperation};
+
+use std::sync::Arc;
+
+use crate::maths::sampler::Sampler;
 
 use crate::maths::Intersection;
 use crate::maths::lines::Line;
@@ -18,21 +29,65 @@ pub struct SurfaceType {
     pub opaqueness: f64,
     pub blocks_light: bool,
     pub full_bright: bool,
+    pub emission: Colour,
 }
 
 #[allow(dead_code)]
 impl SurfaceType {
     pub fn new(diffusiveness: f64, reflectiveness: f64, transparency: f64, refractive_index: f64, blocks_light: bool, full_bright: bool) -> Self {
-        Self { diffuseness: diffusiveness, reflectiveness, transparency, refractive_index, opaqueness: 1.0 - transparency - reflectiveness, blocks_light, full_bright }
+        Self { diffuseness: diffusiveness, reflectiveness, transparency, refractive_index, opaqueness: 1.0 - transparency - reflectiveness, blocks_light, full_bright, emission: Colour::BLACK }
+    }
+
+    pub fn new_emissive(emission: &Colour, strength: f64) -> Self {
+        Self { diffuseness: 0.0, reflectiveness: 0.0, transparency: 0.0, refractive_index: 1.0, opaqueness: 1.0, blocks_light: true, full_bright: false, emission: emission * strength }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        !self.emission.is_black()
+    }
+
+    // Whether shadow rays are stopped or tinted by this surface - transparent surfaces tint them even if they don't block light
+    pub fn casts_shadows(&self) -> bool {
+        self.blocks_light || self.transparency > 0.0
     }
 }
 
 pub trait Object {
     fn as_any(&self) -> &dyn Object;
     fn get_surface_type(&self) -> &SurfaceType;
+    // Material at a particular hit, for objects made of several surfaces
+    fn get_surface_type_at(&self, _intersection: &Intersection) -> &SurfaceType { self.get_surface_type() }
     fn get_intersections(&self, line: &Line) -> Vec<Intersection>;
     fn get_normal(&self, intersection: &Intersection) -> V3;
     fn get_colour(&self, intersection: &Intersection) -> &Colour;
     fn get_reflection_line(&self, line: &Line, intersection: &Intersection) -> Line;
     fn get_transparent_line(&self, line: &Line, intersection: &Intersection) -> Line;
-}
\ No newline at end of file
+
+    // Random point on the surface visible from 'towards' - (position, normal, pdf per unit area)
+    fn get_random_point(&self, _towards: &V3, _rng: &mut dyn Sampler) -> Option<(V3, V3, f64)> { None }
+    fn get_random_point_pdf(&self, _towards: &V3, _point: &V3) -> f64 { 0.0 }
+
+    // Axis aligned (min, max), None if unbounded
+    fn get_bounds(&self) -> Option<(V3, V3)> { None }
+    fn get_surface_area(&self) -> f64 { 0.0 }
+
+    // Moves animated objects to where they are at 'time' seconds, through a shared reference so instances of them move too
+    fn set_time(&self, _time: f64) {}
+}
+
+// Lets a scene's shared objects be used directly when they don't need moving
+impl<T: Object + ?Sized> Object for Arc<T> {
+    fn as_any(&self) -> &dyn Object { self.as_ref().as_any() }
+    fn get_surface_type(&self) -> &SurfaceType { self.as_ref().get_surface_type() }
+    fn get_surface_type_at(&self, intersection: &Intersection) -> &SurfaceType { self.as_ref().get_surface_type_at(intersection) }
+    fn get_intersections(&self, line: &Line) -> Vec<Intersection> { self.as_ref().get_intersections(line) }
+    fn get_normal(&self, intersection: &Intersection) -> V3 { self.as_ref().get_normal(intersection) }
+    fn get_colour(&self, intersection: &Intersection) -> &Colour { self.as_ref().get_colour(intersection) }
+    fn get_reflection_line(&self, line: &Line, intersection: &Intersection) -> Line { self.as_ref().get_reflection_line(line, intersection) }
+    fn get_transparent_line(&self, line: &Line, intersection: &Intersection) -> Line { self.as_ref().get_transparent_line(line, intersection) }
+    fn get_random_point(&self, towards: &V3, rng: &mut dyn Sampler) -> Option<(V3, V3, f64)> { self.as_ref().get_random_point(towards, rng) }
+    fn get_random_point_pdf(&self, towards: &V3, point: &V3) -> f64 { self.as_ref().get_random_point_pdf(towards, point) }
+    fn get_bounds(&self) -> Option<(V3, V3)> { self.as_ref().get_bounds() }
+    fn get_surface_area(&self) -> f64 { self.as_ref().get_surface_area() }
+    fn set_time(&self, time: f64) { self.as_ref().set_time(time) }
+}
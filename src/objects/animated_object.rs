@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::animation::Track;
+use crate::colour::Colour;
+use crate::maths::{vectors::V3, lines::{Line, SizedLine}, quaternion::Quaternion, sampler::Sampler, transform::Transform, Intersection};
+
+use super::{Object, SurfaceType};
+
+// Moves and turns another object over time - rays are taken into the object's own space rather than rebuilding it every frame
+// Each ray sees the object where it is at the ray's time, so moving objects blur over the camera's shutter interval
+#[allow(dead_code)]
+pub struct AnimatedObject {
+    object: Box<dyn Object + Sync + Send>,
+    pivot: V3, // Point in the object's own space that rotations turn about
+    pub translation: Track<V3>,
+    pub rotation: Track<Quaternion>,
+    time: AtomicU64, // f64 bits
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pose {
+    translation: V3,
+    rotation: Quaternion,
+}
+
+#[allow(dead_code)]
+impl AnimatedObject {
+    pub fn new(object: Box<dyn Object + Sync + Send>, pivot: &V3, translation: Track<V3>, rotation: Track<Quaternion>) -> Self {
+        Self { object, pivot: pivot.clone(), translation, rotation, time: AtomicU64::new(0.0f64.to_bits()) }
+    }
+
+    pub fn get_time(&self) -> f64 {
+        f64::from_bits(self.time.load(Ordering::Relaxed))
+    }
+
+    fn evaluate(&self, time: f64) -> Pose {
+        Pose {
+            translation: self.translation.get(time).unwrap_or(V3::ZERO),
+            rotation: self.rotation.get(time).unwrap_or(Quaternion::IDENTITY).normalised(),
+        }
+    }
+
+    // Where the object is for a ray travelling 'offset' seconds after the current time
+    fn get_pose(&self, offset: f64) -> Pose {
+        self.evaluate(self.get_time() + offset)
+    }
+
+    fn to_local_point(&self, pose: &Pose, point: &V3) -> V3 {
+        pose.rotation.conjugate().rotate(&(point - pose.translation - self.pivot)) + self.pivot
+    }
+
+    fn to_world_point(&self, pose: &Pose, point: &V3) -> V3 {
+        pose.rotation.rotate(&(point - self.pivot)) + self.pivot + pose.translation
+    }
+
+    fn get_transform(&self, pose: &Pose) -> Transform {
+        Transform::translation(&(self.pivot + pose.translation)) * Transform::rotation(&pose.rotation) * Transform::translation(&(self.pivot * -1.0))
+    }
+
+    fn to_local_line(&self, pose: &Pose, line: &Line) -> Line {
+        Line::new(&self.to_local_point(pose, &line.point), &pose.rotation.conjugate().rotate(&line.vector)).at_time(line.time)
+    }
+
+    fn to_world_line(&self, pose: &Pose, line: &Line) -> Line {
+        Line::new(&self.to_world_point(pose, &line.point), &pose.rotation.rotate(&line.vector)).at_time(line.time)
+    }
+
+    // The transform is rigid, so distances along the ray are the same in both spaces
+    fn to_local_intersection(&self, pose: &Pose, intersection: &Intersection) -> Intersection {
+        Intersection {
+            sized_line: SizedLine::from_line(&self.to_local_line(pose, &intersection.sized_line.line), intersection.sized_line.scale),
+            position: self.to_local_point(pose, &intersection.position),
+            ..*intersection
+        }
+    }
+
+    fn to_world_intersection(&self, pose: &Pose, intersection: &Intersection) -> Intersection {
+        Intersection {
+            sized_line: SizedLine::from_line(&self.to_world_line(pose, &intersection.sized_line.line), intersection.sized_line.scale),
+            position: self.to_world_point(pose, &intersection.position),
+            ..*intersection
+        }
+    }
+}
+
+impl Object for AnimatedObject {
+    fn as_any(&self) -> &dyn Object { self }
+
+    fn get_surface_type(&self) -> &SurfaceType {
+        self.object.get_surface_type()
+    }
+
+    fn get_surface_type_at(&self, intersection: &Intersection) -> &SurfaceType {
+        let pose = self.get_pose(intersection.sized_line.line.time);
+        self.object.get_surface_type_at(&self.to_local_intersection(&pose, intersection))
+    }
+
+    fn get_intersections(&self, line: &Line) -> Vec<Intersection> {
+        let pose = self.get_pose(line.time);
+        self.object.get_intersections(&self.to_local_line(&pose, line)).iter().map(|i| self.to_world_intersection(&pose, i)).collect()
+    }
+
+    fn get_normal(&self, intersection: &Intersection) -> V3 {
+        let pose = self.get_pose(intersection.sized_line.line.time);
+        pose.rotation.rotate(&self.object.get_normal(&self.to_local_intersection(&pose, intersection)))
+    }
+
+    fn get_colour(&self, intersection: &Intersection) -> &Colour {
+        let pose = self.get_pose(intersection.sized_line.line.time);
+        self.object.get_colour(&self.to_local_intersection(&pose, intersection))
+    }
+
+    fn get_reflection_line(&self, line: &Line, intersection: &Intersection) -> Line {
+        let pose = self.get_pose(line.time);
+        self.to_world_line(&pose, &self.object.get_reflection_line(&self.to_local_line(&pose, line), &self.to_local_intersection(&pose, intersection))).at_time(line.time)
+    }
+
+    fn get_transparent_line(&self, line: &Line, intersection: &Intersection) -> Line {
+        let pose = self.get_pose(line.time);
+        self.to_world_line(&pose, &self.object.get_transparent_line(&self.to_local_line(&pose, line), &self.to_local_intersection(&pose, intersection))).at_time(line.time)
+    }
+
+    // Light sampling sees the object where it is at the current time, ignoring the shutter
+    fn get_random_point(&self, towards: &V3, rng: &mut dyn Sampler) -> Option<(V3, V3, f64)> {
+        let pose = self.get_pose(0.0);
+        let (point, normal, pdf) = self.object.get_random_point(&self.to_local_point(&pose, towards), rng)?;
+        Some((self.to_world_point(&pose, &point), pose.rotation.rotate(&normal), pdf))
+    }
+
+    fn get_random_point_pdf(&self, towards: &V3, point: &V3) -> f64 {
+        let pose = self.get_pose(0.0);
+        self.object.get_random_point_pdf(&self.to_local_point(&pose, towards), &self.to_local_point(&pose, point))
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        Some(self.get_transform(&self.get_pose(0.0)).transform_bounds(&self.object.get_bounds()?))
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        self.object.get_surface_area()
+    }
+
+    fn set_time(&self, time: f64) {
+        self.object.set_time(time);
+        self.time.store(time.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation::{Track, Interpolation};
+    use crate::colour::Colour;
+    use crate::maths::{vectors::V3, lines::Line, Intersection};
+    use crate::objects::{Object, Sphere, SurfaceType};
+
+    use super::AnimatedObject;
+
+    // Sliding 10 along z over the first second
+    fn get_sliding_sphere() -> AnimatedObject {
+        let sphere = Sphere::new(V3::ZERO, 0.5, Colour::WHITE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false));
+        let translation = Track::new(Interpolation::Linear).with_keyframe(0.0, V3::ZERO).with_keyframe(1.0, V3::RIGHT * 10.0);
+        AnimatedObject::new(Box::new(sphere), &V3::ZERO, translation, Track::new(Interpolation::Linear))
+    }
+
+    #[test]
+    fn animated_object_follows_its_track() {
+        let object = get_sliding_sphere();
+        let ray = Line::new(&V3::new(-5.0, 0.0, 10.0), &V3::FORWARD);
+        assert!(object.get_intersections(&ray).is_empty());
+
+        object.set_time(1.0);
+        let hit = *Intersection::closest(&object.get_intersections(&ray)).unwrap();
+        assert!((hit.sized_line.length() - 4.5).abs() < 1e-9);
+        assert!((hit.position - V3::new(-0.5, 0.0, 10.0)).magnitude() < 1e-9);
+        assert!((object.get_normal(&hit).normalised() - V3::BACK).magnitude() < 1e-9);
+    }
+
+    // Each ray sees the sphere where it is at the ray's own time, which is what blurs it over the shutter
+    #[test]
+    fn rays_hit_the_object_where_it_is_at_their_time() {
+        let object = get_sliding_sphere();
+        let ray = Line::new(&V3::new(-5.0, 0.0, 5.0), &V3::FORWARD);
+
+        // The sphere only crosses z = 5 between 0.45 and 0.55 seconds
+        for (time, is_hit) in [(0.0, false), (0.44, false), (0.46, true), (0.5, true), (0.54, true), (0.56, false), (1.0, false)] {
+            assert_eq!(!object.get_intersections(&ray.at_time(time)).is_empty(), is_hit, "{}", time);
+        }
+
+        // Times are relative to the object's own time
+        object.set_time(0.25);
+        assert!(!object.get_intersections(&ray.at_time(0.25)).is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+use crate::colour::Colour;
+use crate::maths::{vectors::V3, lines::Line, Intersection};
+
+use super::{Object, SurfaceType};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference, // Left with right cut out of it
+}
+
+#[allow(dead_code)]
+impl CsgOperation {
+    fn is_inside(&self, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => inside_left || inside_right,
+            CsgOperation::Intersection => inside_left && inside_right,
+            CsgOperation::Difference => inside_left && !inside_right,
+        }
+    }
+}
+
+// Combines two closed objects - hits along a line pair up into entry and exit intervals, which are merged by the operation
+// Each surface keeps the colour and material of the object it came from, so cut faces show the inside of the right object
+// Emissive objects can't be combined, as there's no way to sample points on only the parts of them that are left
+#[allow(dead_code)]
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<dyn Object + Sync + Send>,
+    right: Box<dyn Object + Sync + Send>,
+    surface_type: SurfaceType, // For checks made without a hit, blocking light or transparent if either object is
+}
+
+// A surface of one of the objects, with the hit as that object made it
+#[derive(Debug, Clone, Copy)]
+struct Boundary {
+    hit: Intersection,
+    is_left: bool,
+}
+
+#[allow(dead_code)]
+impl Csg {
+    // None if either object is emissive
+    pub fn new(operation: CsgOperation, left: Box<dyn Object + Sync + Send>, right: Box<dyn Object + Sync + Send>) -> Option<Self> {
+        let (left_surface, right_surface) = (left.get_surface_type(), right.get_surface_type());
+        if left_surface.is_emissive() || right_surface.is_emissive() { return None; }
+
+        // Either object's light blocking or transparency is kept so the combined object isn't skipped by rays that need them
+        let surface_type = SurfaceType {
+            blocks_light: left_surface.blocks_light || right_surface.blocks_light,
+            transparency: left_surface.transparency.max(right_surface.transparency),
+            ..*left_surface
+        };
+        Some(Self { operation, left, right, surface_type })
+    }
+
+    pub fn union(left: Box<dyn Object + Sync + Send>, right: Box<dyn Object + Sync + Send>) -> Option<Self> {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn Object + Sync + Send>, right: Box<dyn Object + Sync + Send>) -> Option<Self> {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Box<dyn Object + Sync + Send>, right: Box<dyn Object + Sync + Send>) -> Option<Self> {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+
+    pub fn get_operation(&self) -> CsgOperation {
+        self.operation
+    }
+
+    fn get_child(&self, is_left: bool) -> &(dyn Object + Sync + Send) {
+        if is_left { self.left.as_ref() } else { self.right.as_ref() }
+    }
+
+    // Every hit along the line sorted by distance - closed objects alternate between entering and leaving
+    // An unpaired last hit (e.g. from a plane) is dropped, as it doesn't bound an interval
+    fn get_sorted_hits(object: &(dyn Object + Sync + Send), line: &Line) -> Vec<Intersection> {
+        let mut hits = object.get_intersections(line);
+        hits.sort_by(|a, b| a.sized_line.scale.total_cmp(&b.sized_line.scale));
+        if hits.len() % 2 == 1 { hits.pop(); }
+        hits
+    }
+
+    // Surfaces of either object where the line crosses between inside and outside of the combined shape
+    // Each hit's part records which object it came from in its lowest bit, above the object's own part
+    fn get_boundaries(&self, line: &Line) -> Vec<Boundary> {
+        let mut events: Vec<Boundary> = Self::get_sorted_hits(self.left.as_ref(), line).into_iter().map(|hit| Boundary { hit, is_left: true })
+            .chain(Self::get_sorted_hits(self.right.as_ref(), line).into_iter().map(|hit| Boundary { hit, is_left: false }))
+            .collect();
+        events.sort_by(|a, b| a.hit.sized_line.scale.total_cmp(&b.hit.sized_line.scale));
+
+        let (mut inside_left, mut inside_right) = (false, false);
+        let mut boundaries = Vec::new();
+
+        for event in events {
+            let was_inside = self.operation.is_inside(inside_left, inside_right);
+            if event.is_left { inside_left = !inside_left; } else { inside_right = !inside_right; }
+
+            if self.operation.is_inside(inside_left, inside_right) != was_inside {
+                let part = (event.hit.part << 1) | (!event.is_left as u32);
+                boundaries.push(Boundary { hit: Intersection { part, ..event.hit }, ..event });
+            }
+        }
+
+        boundaries
+    }
+
+    // Undoes 'get_boundaries' for one of its hits
+    fn get_boundary(&self, intersection: &Intersection) -> Boundary {
+        Boundary { hit: Intersection { part: intersection.part >> 1, ..*intersection }, is_left: intersection.part & 1 == 0 }
+    }
+}
+
+impl Object for Csg {
+    fn as_any(&self) -> &dyn Object { self }
+
+    fn get_surface_type(&self) -> &SurfaceType {
+        &self.surface_type
+    }
+
+    fn get_surface_type_at(&self, intersection: &Intersection) -> &SurfaceType {
+        let boundary = self.get_boundary(intersection);
+        self.get_child(boundary.is_left).get_surface_type_at(&boundary.hit)
+    }
+
+    fn get_intersections(&self, line: &Line) -> Vec<Intersection> {
+        self.get_boundaries(line).iter().map(|b| b.hit).collect()
+    }
+
+    // Faces cut by the right object point into it, so their normals are flipped to face out of the result
+    fn get_normal(&self, intersection: &Intersection) -> V3 {
+        let boundary = self.get_boundary(intersection);
+        let normal = self.get_child(boundary.is_left).get_normal(&boundary.hit);
+        if !boundary.is_left && self.operation == CsgOperation::Difference { normal * -1.0 } else { normal }
+    }
+
+    fn get_colour(&self, intersection: &Intersection) -> &Colour {
+        let boundary = self.get_boundary(intersection);
+        self.get_child(boundary.is_left).get_colour(&boundary.hit)
+    }
+
+    fn get_reflection_line(&self, line: &Line, intersection: &Intersection) -> Line {
+        Line::new(&intersection.position, &line.vector.reflected(&self.get_normal(intersection))).at_time(line.time)
+    }
+
+    // Bent at this surface only, by the refractive index of whichever object it came from, as the objects' own refraction
+    // would carry the ray through the whole of them - the next ray finds where it really leaves the combined shape
+    fn get_transparent_line(&self, line: &Line, intersection: &Intersection) -> Line {
+        let boundary = self.get_boundary(intersection);
+        let refractive_index = self.get_child(boundary.is_left).get_surface_type_at(&boundary.hit).refractive_index;
+        let (direction, normal) = (line.vector.normalised(), self.get_normal(intersection).normalised());
+
+        // Normals face out of the combined shape, so rays going against them are entering it
+        let (normal, eta) = if direction.dot(&normal) < 0.0 { (normal, 1.0 / refractive_index) } else { (normal * -1.0, refractive_index) };
+        let new_direction = direction.refracted(&normal, eta).unwrap_or_else(|| direction.reflected(&normal));
+
+        Line::new(&intersection.position, &new_direction).at_time(line.time)
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        match self.operation {
+            CsgOperation::Union => {
+                let (left, right) = (self.left.get_bounds()?, self.right.get_bounds()?);
+                Some((left.0.min(&right.0), left.1.max(&right.1)))
+            },
+            CsgOperation::Intersection => match (self.left.get_bounds(), self.right.get_bounds()) {
+                (Some(left), Some(right)) => Some((left.0.max(&right.0), left.1.min(&right.1))),
+                (left, right) => left.or(right),
+            },
+            CsgOperation::Difference => self.left.get_bounds(),
+        }
+    }
+
+    fn set_time(&self, time: f64) {
+        self.left.set_time(time);
+        self.right.set_time(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colour::Colour;
+    use crate::maths::{vectors::V3, lines::Line, Intersection};
+    use crate::objects::{Object, Sphere, SurfaceType};
+
+    use super::{Csg, CsgOperation};
+
+    #[test]
+    fn csg_operations_combine_intervals() {
+        let diffuse = SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false);
+        let mirror = SurfaceType::new(0.0, 1.0, 0.0, 1.0, true, false);
+        let get_csg = |operation| Csg::new(operation,
+            Box::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, diffuse)),
+            Box::new(Sphere::new(V3::FORWARD, 1.0, Colour::RED, mirror))).unwrap();
+
+        // Spheres span 4..6 and 5..7 along the ray
+        let ray = Line::new(&V3::new(-5.0, 0.0, 0.0), &V3::FORWARD);
+        for (operation, expected) in [(CsgOperation::Union, [4.0, 7.0]), (CsgOperation::Intersection, [5.0, 6.0]), (CsgOperation::Difference, [4.0, 5.0])] {
+            let scales: Vec<f64> = get_csg(operation).get_intersections(&ray).iter().map(|i| i.sized_line.scale).collect();
+            assert_eq!(scales.len(), 2, "{:?}", operation);
+            assert!((scales[0] - expected[0]).abs() < 1e-9 && (scales[1] - expected[1]).abs() < 1e-9, "{:?}: {:?}", operation, scales);
+        }
+
+        // The cut face takes the right sphere's colour and material, with its normal facing out of the result
+        let difference = get_csg(CsgOperation::Difference);
+        let hits = difference.get_intersections(&ray);
+        let exit = hits.iter().find(|i| (i.sized_line.scale - 5.0).abs() < 1e-9).unwrap();
+        assert!((difference.get_normal(exit).normalised() - V3::FORWARD).magnitude() < 1e-9);
+        assert!(difference.get_colour(exit).luminance() == Colour::RED.luminance());
+        assert!(difference.get_surface_type_at(exit).reflectiveness == 1.0);
+
+        let entry = hits.iter().find(|i| (i.sized_line.scale - 4.0).abs() < 1e-9).unwrap();
+        assert!((difference.get_normal(entry).normalised() - V3::BACK).magnitude() < 1e-9);
+        assert!(difference.get_surface_type_at(entry).diffuseness == 1.0);
+
+        // Rays through only the cut away part miss
+        assert!(difference.get_intersections(&Line::new(&V3::new(1.5, -5.0, 0.0), &V3::UP)).is_empty());
+    }
+
+    #[test]
+    fn nested_hits_find_their_surface() {
+        let get_sphere = |centre: V3, reflectiveness: f64| -> Box<dyn Object + Sync + Send> {
+            Box::new(Sphere::new(centre, 1.0, Colour::WHITE, SurfaceType::new(1.0 - reflectiveness, reflectiveness, 0.0, 1.0, true, false)))
+        };
+        let inner = Csg::union(get_sphere(V3::ZERO, 0.25), get_sphere(V3::FORWARD * 1.5, 0.5)).unwrap();
+        let csg = Csg::union(get_sphere(V3::FORWARD * -1.5, 0.0), Box::new(inner)).unwrap();
+
+        // Enters the first sphere and leaves the third
+        let hits = csg.get_intersections(&Line::new(&V3::new(-5.0, 0.0, 0.0), &V3::FORWARD));
+        assert_eq!(hits.len(), 2);
+        assert!(csg.get_surface_type_at(&hits[0]).reflectiveness == 0.0, "{:?}", hits[0]);
+        assert!(csg.get_surface_type_at(&hits[1]).reflectiveness == 0.5, "{:?}", hits[1]);
+        assert!((csg.get_normal(&hits[1]).normalised() - V3::FORWARD).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn refracts_at_each_surface_of_the_combined_shape() {
+        let get_lens = |refractive_index| Csg::intersection(
+            Box::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, SurfaceType::new(0.0, 0.0, 1.0, refractive_index, false, false))),
+            Box::new(Sphere::new(V3::FORWARD, 1.0, Colour::WHITE, SurfaceType::new(0.0, 0.0, 1.0, refractive_index, false, false)))).unwrap();
+        let ray = Line::new(&V3::new(-5.0, 0.2, 0.0), &V3::FORWARD);
+
+        // Without bending, the ray carries straight on from where it entered
+        let lens = get_lens(1.0);
+        let entry = lens.get_intersections(&ray)[0];
+        let line = lens.get_transparent_line(&ray, &entry);
+        assert!((line.point - entry.position).magnitude() < 1e-9 && (line.vector - V3::FORWARD).magnitude() < 1e-9, "{:?}", line);
+
+        // Bent towards the axis going in, and leaving through the lens' far face rather than the far side of the sphere it entered
+        let lens = get_lens(1.5);
+        let line = lens.get_transparent_line(&ray, &entry);
+        assert!(line.vector.y < 0.0, "{:?}", line);
+
+        let exit = *Intersection::closest_bounded(&lens.get_intersections(&line), 1e-6, f64::INFINITY).unwrap();
+        assert!(exit.position.x > 0.0 && (exit.position.magnitude() - 1.0).abs() < 1e-9, "{:?}", exit);
+        let out = lens.get_transparent_line(&line, &exit);
+        assert!(out.vector.y < line.vector.y, "{:?} {:?}", line, out);
+    }
+
+    #[test]
+    fn emissive_objects_cant_be_combined() {
+        let diffuse = SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false);
+        let glowing = SurfaceType::new_emissive(&Colour::WHITE, 1.0);
+
+        assert!(Csg::union(Box::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, diffuse)), Box::new(Sphere::new(V3::FORWARD, 1.0, Colour::WHITE, glowing))).is_none());
+        assert!(Csg::union(Box::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, diffuse)), Box::new(Sphere::new(V3::FORWARD, 1.0, Colour::WHITE, diffuse))).is_some());
+    }
+
+    #[test]
+    fn blocks_light_if_either_object_does() {
+        let clear = SurfaceType::new(0.0, 0.0, 1.0, 1.0, false, false);
+        let solid = SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false);
+        let csg = Csg::union(Box::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, clear)), Box::new(Sphere::new(V3::FORWARD * 1.5, 1.0, Colour::WHITE, solid))).unwrap();
+        assert!(csg.get_surface_type().blocks_light);
+
+        // Each hit still has the surface of the sphere it's on
+        let hits = csg.get_intersections(&Line::new(&V3::new(-5.0, 0.0, 0.0), &V3::FORWARD));
+        assert_eq!(hits.len(), 2);
+        assert!(!csg.get_surface_type_at(&hits[0]).blocks_light && csg.get_surface_type_at(&hits[1]).blocks_light);
+    }
+
+    #[test]
+    fn bounds_follow_the_operation() {
+        let diffuse = SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false);
+        let get_bounds = |operation| Csg::new(operation,
+            Box::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, diffuse)),
+            Box::new(Sphere::new(V3::FORWARD, 1.0, Colour::WHITE, diffuse))).unwrap().get_bounds().unwrap();
+
+        for (operation, min_x, max_x) in [(CsgOperation::Union, -1.0, 2.0), (CsgOperation::Intersection, 0.0, 1.0), (CsgOperation::Difference, -1.0, 1.0)] {
+            let (min, max) = get_bounds(operation);
+            assert!(min.x == min_x && max.x == max_x && min.y == -1.0 && max.y == 1.0, "{:?}: {:?} {:?}", operation, min, max);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::maths::{vectors::V3, lines::{Line, SizedLine}, transform::Transform, sampler::Sampler, Intersection};
+
+use super::{Object, SurfaceType};
+
+// Shared geometry placed in the scene by a transform - the object is defined once in its own space and reused
+#[allow(dead_code)]
+pub struct Instance {
+    object: Arc<dyn Object + Sync + Send>,
+    transform: Transform, // Object space to world space
+}
+
+#[allow(dead_code)]
+impl Instance {
+    pub fn new(object: Arc<dyn Object + Sync + Send>, transform: Transform) -> Self {
+        Self { object, transform }
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    // Vectors aren't normalised, so distances along the ray are the same in both spaces
+    fn to_local_line(&self, line: &Line) -> Line {
+        Line::new(&self.transform.inverse_transform_point(&line.point), &self.transform.inverse_transform_vector(&line.vector)).at_time(line.time)
+    }
+
+    fn to_world_line(&self, line: &Line) -> Line {
+        Line::new(&self.transform.transform_point(&line.point), &self.transform.transform_vector(&line.vector)).at_time(line.time)
+    }
+
+    fn to_local_intersection(&self, intersection: &Intersection) -> Intersection {
+        Intersection {
+            sized_line: SizedLine::from_line(&self.to_local_line(&intersection.sized_line.line), intersection.sized_line.scale),
+            position: self.transform.inverse_transform_point(&intersection.position),
+            ..*intersection
+        }
+    }
+
+    fn to_world_intersection(&self, intersection: &Intersection) -> Intersection {
+        Intersection {
+            sized_line: SizedLine::from_line(&self.to_world_line(&intersection.sized_line.line), intersection.sized_line.scale),
+            position: self.transform.transform_point(&intersection.position),
+            ..*intersection
+        }
+    }
+
+    // Area pdfs are per unit area, so they shrink by however much the surface around the point is stretched
+    fn to_world_pdf(&self, pdf: f64, local_normal: &V3) -> f64 {
+        let scale = self.transform.get_area_scale(local_normal);
+        if scale > 0.0 { pdf / scale } else { 0.0 }
+    }
+}
+
+impl Object for Instance {
+    fn as_any(&self) -> &dyn Object { self }
+
+    fn get_surface_type(&self) -> &SurfaceType {
+        self.object.get_surface_type()
+    }
+
+    fn get_surface_type_at(&self, intersection: &Intersection) -> &SurfaceType {
+        self.object.get_surface_type_at(&self.to_local_intersection(intersection))
+    }
+
+    fn get_intersections(&self, line: &Line) -> Vec<Intersection> {
+        self.object.get_intersections(&self.to_local_line(line)).iter().map(|i| self.to_world_intersection(i)).collect()
+    }
+
+    fn get_normal(&self, intersection: &Intersection) -> V3 {
+        self.transform.transform_normal(&self.object.get_normal(&self.to_local_intersection(intersection)))
+    }
+
+    fn get_colour(&self, intersection: &Intersection) -> &Colour {
+        self.object.get_colour(&self.to_local_intersection(intersection))
+    }
+
+    // Reflected in world space, as non-uniform scale doesn't preserve angles
+    fn get_reflection_line(&self, line: &Line, intersection: &Intersection) -> Line {
+        Line::new(&intersection.position, &line.vector.reflected(&self.get_normal(intersection))).at_time(line.time)
+    }
+
+    fn get_transparent_line(&self, line: &Line, intersection: &Intersection) -> Line {
+        self.to_world_line(&self.object.get_transparent_line(&self.to_local_line(line), &self.to_local_intersection(intersection))).at_time(line.time)
+    }
+
+    fn get_random_point(&self, towards: &V3, rng: &mut dyn Sampler) -> Option<(V3, V3, f64)> {
+        let (point, normal, pdf) = self.object.get_random_point(&self.transform.inverse_transform_point(towards), rng)?;
+        Some((self.transform.transform_point(&point), self.transform.transform_normal(&normal).normalised(), self.to_world_pdf(pdf, &normal)))
+    }
+
+    // The normal at 'point' comes from the object, found along the line from 'towards' like a hit would be
+    fn get_random_point_pdf(&self, towards: &V3, point: &V3) -> f64 {
+        let (local_towards, local_point) = (self.transform.inverse_transform_point(towards), self.transform.inverse_transform_point(point));
+        let pdf = self.object.get_random_point_pdf(&local_towards, &local_point);
+        if pdf == 0.0 { return 0.0; }
+
+        let hit = Intersection::new(&Line::new(&local_towards, &(local_point - local_towards)), 1.0, &local_point);
+        self.to_world_pdf(pdf, &self.object.get_normal(&hit))
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        Some(self.transform.transform_bounds(&self.object.get_bounds()?))
+    }
+
+    // Only weights how often the object is picked as a light, so non-uniform scales use the stretch averaged over the axes
+    fn get_surface_area(&self) -> f64 {
+        let area_scale = [V3::FORWARD, V3::UP, V3::RIGHT].iter().map(|axis| self.transform.get_area_scale(axis)).sum::<f64>() / 3.0;
+        self.object.get_surface_area() * area_scale
+    }
+
+    fn set_time(&self, time: f64) {
+        self.object.set_time(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::f64::consts::PI;
+
+    use crate::colour::{Colour, colour_getters::SolidColour};
+    use crate::maths::{vectors::V3, lines::Line, transform::Transform, quaternion::Quaternion, Intersection};
+    use crate::objects::{Object, Sphere, Plane, SurfaceType};
+    use crate::lights::{ObjectLight, tests::{assert_samples_match_intersect, assert_pdf_integrates_to_one}};
+
+    use super::Instance;
+
+    #[test]
+    fn scaled_instance_transforms_hits_and_normals() {
+        let sphere: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 1.0, Colour::WHITE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false)));
+        let transform = Transform::translation(&V3::new(0.0, 0.0, 5.0)) * Transform::rotation(&Quaternion::from_axis_angle(&V3::UP, PI / 2.0)) * Transform::scale(&V3::new(2.0, 1.0, 1.0));
+        let instance = Instance::new(sphere, transform);
+
+        // Stretched along x then turned onto z, so the ellipsoid reaches from z = 3 to 7
+        let along = Line::new(&V3::new(0.0, 0.0, -5.0), &V3::RIGHT);
+        let hit = *Intersection::closest(&instance.get_intersections(&along)).unwrap();
+        assert!((hit.position - V3::new(0.0, 0.0, 3.0)).magnitude() < 1e-9);
+        assert!((instance.get_normal(&hit).normalised() - V3::LEFT).magnitude() < 1e-9);
+
+        // Off axis the normal has to follow the ellipsoid's gradient, not just the sphere's normal rotated
+        let across = Line::new(&V3::new(0.0, -5.0, 6.0), &V3::UP);
+        let hit = *Intersection::closest(&instance.get_intersections(&across)).unwrap();
+        let gradient = V3::new(0.0, 2.0 * hit.position.y, (hit.position.z - 5.0) / 2.0).normalised();
+        assert!((hit.position.y + (0.75f64).sqrt()).abs() < 1e-9);
+        assert!((instance.get_normal(&hit).normalised() - gradient).magnitude() < 1e-9);
+
+        let (min, max) = instance.get_bounds().unwrap();
+        assert!((min - V3::new(-1.0, -1.0, 3.0)).magnitude() < 1e-9 && (max - V3::new(1.0, 1.0, 7.0)).magnitude() < 1e-9);
+    }
+
+    fn get_stretched_light() -> Instance {
+        let sphere: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 1.0, Colour::BLACK, SurfaceType::new_emissive(&Colour::WHITE, 1.0)));
+        Instance::new(sphere, Transform::rotation(&Quaternion::from_axis_angle(&V3::new(1.0, 1.0, 0.0), PI / 3.0)) * Transform::scale(&V3::new(3.0, 1.0, 0.5)))
+    }
+
+    #[test]
+    fn stretched_light_samples_match_their_pdf() {
+        let instance = get_stretched_light();
+        assert_samples_match_intersect(&ObjectLight::new(&instance), &V3::new(2.0, 4.0, -3.0));
+    }
+
+    // Spheres only sample the half facing the destination, so a panel is used to check the whole pdf
+    #[test]
+    fn stretched_panel_pdf_integrates_to_one() {
+        let panel: Arc<dyn Object + Sync + Send> = Arc::new(Plane::new(&V3::ZERO, &V3::FORWARD, &V3::RIGHT, Some((-1.0, -1.0, 1.0, 1.0)),
+            Box::new(SolidColour { colour: Colour::BLACK }), SurfaceType::new_emissive(&Colour::WHITE, 1.0)));
+        let instance = Instance::new(panel, Transform::rotation(&Quaternion::from_axis_angle(&V3::new(1.0, 0.0, 1.0), 0.4)) * Transform::scale(&V3::new(3.0, 1.0, 0.5)));
+
+        assert_samples_match_intersect(&ObjectLight::new(&instance), &V3::new(0.5, 2.0, 0.3));
+        assert_pdf_integrates_to_one(&ObjectLight::new(&instance), &V3::new(0.5, 2.0, 0.3));
+    }
+}
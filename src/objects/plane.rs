@@ -1,3 +1,5 @@
+use crate::maths::sampler::Sampler;
+
 use crate::colour::Colour;
 use crate::maths::lines::Line;
 use crate::maths::Intersection;
@@ -12,14 +14,14 @@ pub struct Plane {
     pub vector_one: V3,
     pub vector_two: V3,
     pub limits: Option<(f64, f64, f64, f64)>, // x-min, y-min, x-max, y-max
-    colour_getter: Box<dyn ColourGetter + Sync>,
+    colour_getter: Box<dyn ColourGetter + Sync + Send>,
     surface_type: SurfaceType,
     cached_sol: f64
 }
 
 #[allow(dead_code)]
 impl Plane {
-    pub fn new(point: &V3, vector_one: &V3, vector_two: &V3, limits: Option<(f64, f64, f64, f64)>, colour_getter: Box<dyn ColourGetter + Sync>, surface_type: SurfaceType) -> Self {
+    pub fn new(point: &V3, vector_one: &V3, vector_two: &V3, limits: Option<(f64, f64, f64, f64)>, colour_getter: Box<dyn ColourGetter + Sync + Send>, surface_type: SurfaceType) -> Self {
         Self {
             point: point.clone(),
             vector_one: vector_one.clone(),
@@ -193,6 +195,44 @@ impl Object for Plane {
         )
     }
 
+    fn get_random_point(&self, towards: &V3, rng: &mut dyn Sampler) -> Option<(V3, V3, f64)> {
+        // Unbounded planes have no finite area to sample
+        let limits = self.limits?;
+
+        let (u, v) = rng.get_2d();
+        let point = self.point + (self.vector_one * (limits.0 + u * (limits.2 - limits.0))) + (self.vector_two * (limits.1 + v * (limits.3 - limits.1)));
+
+        let mut normal = self.vector_one.cross(&self.vector_two).normalised();
+        if normal.dot(&(towards - point)) < 0.0 { normal = normal * -1.0; }
+
+        Some((point, normal, self.get_random_point_pdf(towards, &point)))
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        let limits = self.limits?;
+
+        let corners = [
+            self.point + (self.vector_one * limits.0) + (self.vector_two * limits.1),
+            self.point + (self.vector_one * limits.2) + (self.vector_two * limits.1),
+            self.point + (self.vector_one * limits.0) + (self.vector_two * limits.3),
+            self.point + (self.vector_one * limits.2) + (self.vector_two * limits.3),
+        ];
+
+        Some(corners.iter().fold((corners[0], corners[0]), |b, c| (b.0.min(c), b.1.max(c))))
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        match self.limits {
+            None => f64::INFINITY,
+            Some(limits) => self.vector_one.cross(&self.vector_two).magnitude() * (limits.2 - limits.0) * (limits.3 - limits.1)
+        }
+    }
+
+    fn get_random_point_pdf(&self, _towards: &V3, _point: &V3) -> f64 {
+        if self.limits.is_none() { return 0.0; }
+        1.0 / self.get_surface_area()
+    }
+
     /*
     fn get_colour(&self, incoming_ray: &Line) -> Option<((u8, u8, u8), f64, Option<Line>)> {
         let hit = self.get_intersections(incoming_ray);
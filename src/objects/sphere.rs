@@ -1,3 +1,7 @@
+use std::f64::consts::PI;
+
+use crate::maths::sampler::Sampler;
+
 use crate::colour::Colour;
 use crate::maths::{vectors::V3, lines::Line};
 use crate::maths::Intersection;
@@ -125,6 +129,26 @@ impl Object for Sphere {
         Line::new(&new_point, &new_vector.normalised())
     }
 
+    fn get_random_point(&self, towards: &V3, rng: &mut dyn Sampler) -> Option<(V3, V3, f64)> {
+        // Only the hemisphere facing 'towards' can be seen from it
+        let normal = V3::get_random(&(towards - self.centre), 0.5 * PI, rng);
+        Some((self.centre + (normal * self.radius), normal, 1.0 / (2.0 * PI * self.radius * self.radius)))
+    }
+
+    fn get_bounds(&self) -> Option<(V3, V3)> {
+        let extent = V3::ONE * self.radius;
+        Some((self.centre - extent, self.centre + extent))
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn get_random_point_pdf(&self, towards: &V3, point: &V3) -> f64 {
+        if (point - self.centre).dot(&(towards - self.centre)) <= 0.0 { return 0.0; }
+        1.0 / (2.0 * PI * self.radius * self.radius)
+    }
+
     /*
     fn get_colours(&self, incoming_ray: &Line) -> Option<((u8, u8, u8), f64, Option<Line>)> {
         let intersections = self.get_intersections(incoming_ray);
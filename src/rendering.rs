@@ -4,37 +4,138 @@ use std::{fs, f64::consts::PI, time::Instant};
 pub use camera::Camera;
 use chrono::{Datelike, Timelike};
 use image::{DynamicImage, ImageBuffer, Rgb};
-use rand::rngs::ThreadRng;
-use crate::{maths::{lines::Line, Intersection, vectors::V3}, colour::{Colour}, objects::Object};
+use crate::maths::sampler::Sampler;
+use crate::{maths::{lines::Line, Intersection, vectors::V3}, colour::{Colour}, objects::Object, lights::{Light, LightSample, ObjectLight, EnvironmentLight}, animation::CameraTrack};
 pub mod render_config;
-pub use render_config::RenderConfig;
+pub use render_config::{RenderConfig, LightSampling, LightSelection};
+pub mod light_sampler;
+pub use light_sampler::{LightSampler, LightSource};
+pub mod projection;
+#[allow(unused_imports)]
+pub use projection::{Projection, FisheyeMapping};
+pub mod filter;
+pub use filter::Filter;
+pub mod denoiser;
+pub use denoiser::{Denoiser, FeatureBuffers};
+pub mod tiles;
+pub use tiles::{Tile, RenderTarget, RenderProgress, CancellationToken, PixelState, CheckpointHeader};
+pub mod screenshot_queue;
+pub use screenshot_queue::{ScreenshotQueue, ScreenshotJob, JobStatus};
+
+// Renders into 'target', which must be at the screenshot resolution, then saves the result
+// Pixels already in 'target', e.g. from a loaded checkpoint, are carried on from rather than rendered again
+pub fn take_screenshot(camera: &Camera, render_config: &RenderConfig, target: &RenderTarget, on_progress: &mut dyn FnMut(&RenderProgress), cancellation: &CancellationToken) {
+    let file_name = format!("Render {} {}x{}", get_timestamp(), render_config.screenshot_resolution.0, render_config.screenshot_resolution.1);
+    let checkpoint_path = format!("renders\\{}.checkpoint", file_name);
+    let checkpoint_header = CheckpointHeader::new(camera, render_config);
 
-pub fn take_screenshot(camera: &Camera, render_config: &RenderConfig, _rng: &mut ThreadRng) {
     println!("Rendering screenshot...");
     
     let start = Instant::now();
-    let pixel_data = camera.get_image_threaded(render_config);
+    let mut reported = 0;
+    let mut last_checkpoint = Instant::now();
+    let completed = camera.render_tiles(render_config, true, target, &mut |progress| {
+        let percent = (progress.get_fraction() * 100.0) as u32;
+        if percent / 5 != reported / 5 {
+            reported = percent;
+            match progress.get_eta() {
+                Some(eta) => println!("{}% - {}/{} tiles, ETA {:.0?}", percent, progress.completed_tiles, progress.total_tiles, eta),
+                None => println!("{}%", percent),
+            }
+        }
+
+        if let Some(interval) = render_config.checkpoint_interval {
+            if last_checkpoint.elapsed().as_secs_f64() >= interval {
+                last_checkpoint = Instant::now();
+                save_checkpoint(target, &checkpoint_header, &checkpoint_path);
+            }
+        }
+        on_progress(progress);
+    }, cancellation);
     let time = start.elapsed();
 
+    if !completed {
+        println!("Screenshot cancelled after {:?}", time);
+        if render_config.checkpoint_interval.is_some() { save_checkpoint(target, &checkpoint_header, &checkpoint_path); }
+        return;
+    }
+
+    let (colours, sample_counts) = (target.get_colours(), target.get_sample_counts());
+
     // let start2 = Instant::now();
-    // let pixel_data = camera.get_image(render_config, rng, true, false);
+    // let pixel_data = camera.get_image(render_config, true, false);
     // let time2 = start2.elapsed();
     println!("Render time: {:?}", time);
 
+    let total_samples: u64 = sample_counts.iter().map(|s| *s as u64).sum();
+    println!("Average samples per pixel: {:.2}", total_samples as f64 / sample_counts.len().max(1) as f64);
+
     println!("Saving screenshot...");
-    
+
+    let colours = apply_denoiser(camera, render_config, colours, true);
+    save_image(get_pixel_data(&colours), render_config.screenshot_resolution, &file_name);
+
+    if render_config.save_sample_heatmap {
+        let max_samples = render_config.get_pixel_samples(true);
+        save_image(get_sample_heatmap(&sample_counts, max_samples), render_config.screenshot_resolution, &format!("{} samples", file_name));
+    }
+}
+
+// Renders frames 'frame_0001.png' onwards at 'frame_rate' from 'start' to 'end' seconds, returning false if cancelled
+pub fn render_animation(camera: &Camera, camera_track: &CameraTrack, render_config: &mut RenderConfig, start: f64, end: f64, frame_rate: f64, cancellation: &CancellationToken) -> bool {
+    let frame_count = (((end - start) * frame_rate).round() as u32).max(1);
+
+    let directory = format!("Animation {}", get_timestamp());
+    if fs::create_dir_all(format!("renders\\{}", directory)).is_err() { println!("Creating 'renders\\{}' failed!", directory); }
+
+    println!("Rendering {} frames...", frame_count);
+    let start_time = Instant::now();
+
+    for frame in 0..frame_count {
+        let time = start + frame as f64 / frame_rate;
+        render_config.set_time(time);
+        let frame_camera = camera_track.get_camera(camera, time);
+
+        // Every frame uses the same seed so still parts of the scene don't flicker
+        let target = RenderTarget::new(render_config.screenshot_resolution);
+        let completed = frame_camera.render_tiles(render_config, true, &target, &mut |_| {}, cancellation);
+        if !completed {
+            println!("Animation cancelled on frame {}", frame + 1);
+            return false;
+        }
+
+        let colours = apply_denoiser(&frame_camera, render_config, target.get_colours(), true);
+        save_image(get_pixel_data(&colours), render_config.screenshot_resolution, &format!("{}\\frame_{:0>4}", directory, frame + 1));
+
+        let elapsed = start_time.elapsed();
+        println!("Frame {}/{} at {:.2}s, ETA {:.0?}", frame + 1, frame_count, time, elapsed.mul_f64((frame_count - frame - 1) as f64 / (frame + 1) as f64));
+    }
+
+    println!("Animation time: {:?}", start_time.elapsed());
+    true
+}
+
+// Local time as 'YYYY-MM-DD hh-mm-ss', for naming renders
+fn get_timestamp() -> String {
+    let now = chrono::offset::Local::now();
+    format!("{}-{:0>2}-{:0>2} {:0>2}-{:0>2}-{:0>2}", now.year(), now.month(), now.day(), now.hour(), now.minute(), now.second())
+}
+
+fn save_checkpoint(target: &RenderTarget, header: &CheckpointHeader, path: &str) {
+    let result = fs::create_dir_all("renders").map_err(|e| e.to_string())
+        .and_then(|_| target.save_checkpoint(path, header));
+    match result {
+        Ok(()) => println!("Saved checkpoint to '{}'", path),
+        Err(e) => println!("Saving checkpoint failed: {}", e),
+    }
+}
+
+fn save_image(pixel_data: Vec<u8>, resolution: (u32, u32), file_name: &str) {
     let img = DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(
-        render_config.screenshot_resolution.0, 
-        render_config.screenshot_resolution.1, 
+        resolution.0, 
+        resolution.1, 
         pixel_data
     ).unwrap());
-
-    let now = chrono::offset::Local::now();
-    
-    let file_name = format!("Render {}-{}-{} {}-{}-{} {}x{}", 
-        format!("{:0>2}", now.year()), format!("{:0>2}", now.month()), 
-        format!("{:0>2}", now.day()), format!("{:0>2}", now.hour()), 
-        format!("{:0>2}", now.minute()), format!("{:0>2}", now.second()), render_config.screenshot_resolution.0, render_config.screenshot_resolution.1);
         
     let mut is_err = fs::create_dir_all("renders").is_err();
     if !is_err {
@@ -44,53 +145,290 @@ pub fn take_screenshot(camera: &Camera, render_config: &RenderConfig, _rng: &mut
     else { println!("Saved file to 'renders\\{}.png'", file_name); }
 }
 
-pub fn get_colour(ray: Line, render_config: &RenderConfig, rng: &mut ThreadRng, is_screenshot: bool) -> Colour {
+// Black through red and yellow to white as the sample count approaches the maximum
+fn get_sample_heatmap(sample_counts: &[u32], max_samples: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(sample_counts.len() * 3);
+    for samples in sample_counts {
+        let t = (*samples as f64 / max_samples.max(1) as f64).clamp(0.0, 1.0) * 3.0;
+        let colour = Colour::from_f64(t.min(1.0), (t - 1.0).clamp(0.0, 1.0), (t - 2.0).clamp(0.0, 1.0)).as_u8();
+        data.push(colour.0);
+        data.push(colour.1);
+        data.push(colour.2);
+    }
+    data
+}
+
+pub fn apply_denoiser(camera: &Camera, render_config: &RenderConfig, colours: Vec<Colour>, is_screenshot: bool) -> Vec<Colour> {
+    let enabled = if is_screenshot { render_config.screenshot_denoise } else { render_config.denoise };
+
+    if !enabled { return colours; }
+    denoise(camera, render_config, &colours, is_screenshot)
+}
+
+pub fn denoise(camera: &Camera, render_config: &RenderConfig, colours: &[Colour], is_screenshot: bool) -> Vec<Colour> {
+    let resolution = if is_screenshot { render_config.screenshot_resolution } else { render_config.resolution };
+
+    let features = camera.get_feature_buffers(render_config, is_screenshot);
+    render_config.denoiser.denoise(colours, &features, resolution)
+}
+
+// Linear colours clamped into 8 bit RGB, done last so everything before works on the full range
+pub fn get_pixel_data(colours: &[Colour]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(colours.len() * 3);
+    for colour in colours {
+        let colour = colour.ceiling_limited().as_u8();
+        data.push(colour.0);
+        data.push(colour.1);
+        data.push(colour.2);
+    }
+    data
+}
+
+// Nearest neighbour resize, used to show screenshots in progress in the preview window
+pub fn get_scaled(pixel_data: &[u8], from: (u32, u32), to: (u32, u32)) -> Vec<u8> {
+    let mut data = Vec::with_capacity((to.0 * to.1 * 3) as usize);
+    for y in 0..to.1 {
+        for x in 0..to.0 {
+            let source_x = ((x as u64 * from.0 as u64) / to.0 as u64) as u32;
+            let source_y = ((y as u64 * from.1 as u64) / to.1 as u64) as u32;
+            let i = ((source_y * from.0 + source_x) * 3) as usize;
+            data.extend_from_slice(&pixel_data[i..i + 3]);
+        }
+    }
+    data
+}
+
+// Albedo, normal facing the viewer, and distance at the first hit along 'ray' - (WHITE, ZERO, f64::MAX) if nothing is hit
+pub fn get_features(ray: &Line, render_config: &RenderConfig) -> (Colour, V3, f64) {
+    let mut closest = None;
+
+    for scene_object in &render_config.scene_objects {
+        if let Some(hit) = Intersection::closest_bounded(&scene_object.get_intersections(ray), 0.000001, f64::INFINITY) {
+            let distance = hit.sized_line.length();
+            if closest.as_ref().is_none_or(|(d, _, _)| distance < *d) { closest = Some((distance, *hit, scene_object)); }
+        }
+    }
+
+    match closest {
+        None => (Colour::WHITE, V3::ZERO, f64::MAX),
+        Some((distance, hit, scene_object)) => {
+            let mut normal = scene_object.get_normal(&hit).normalised();
+            if normal.dot(&ray.vector) > 0.0 { normal = normal * -1.0; }
+            (*scene_object.get_colour(&hit), normal, distance)
+        }
+    }
+}
+
+// Distance along 'ray' to the first object it hits
+pub fn get_closest_distance(ray: &Line, render_config: &RenderConfig) -> Option<f64> {
+    let mut closest_dist = None;
+
+    for scene_object in &render_config.scene_objects {
+        if let Some(hit) = Intersection::closest_bounded(&scene_object.get_intersections(ray), 0.000001, f64::INFINITY) {
+            let distance = hit.sized_line.length();
+            if closest_dist.is_none() || distance < closest_dist.unwrap() { closest_dist = Some(distance); }
+        }
+    }
+
+    closest_dist
+}
+
+pub fn get_colour(ray: Line, render_config: &RenderConfig, rng: &mut dyn Sampler, is_screenshot: bool) -> Colour {
     let max_reflections;
     if is_screenshot { max_reflections = render_config.screenshot_max_reflection; }
     else { max_reflections = render_config.max_reflections; }
     get_colour_recursively(ray, render_config, is_screenshot, max_reflections, rng)
 }
 
-fn get_direct_light(render_config: &RenderConfig, hit: &Intersection, scene_object: &Box<dyn Object + Sync>, hit_index: usize) -> Colour {
-    let normal = scene_object.get_normal(hit);
+fn get_shadow_samples(render_config: &RenderConfig, is_screenshot: bool) -> u32 {
+    if is_screenshot { render_config.screenshot_shadow_samples.max(1) }
+    else { render_config.shadow_samples.max(1) }
+}
+
+// Where direct light is being gathered
+struct ShadingPoint<'a> {
+    hit: &'a Intersection,
+    normal: V3,
+    index: usize, // Of the object hit, which can't shadow itself
+    traces_indirect: bool, // Indirect rays are traced from here too, and pick up the environment where they miss and emissive objects they hit
+}
+
+fn get_direct_light(render_config: &RenderConfig, hit: &Intersection, scene_object: &Box<dyn Object + Sync + Send>, hit_index: usize, traces_indirect: bool, is_screenshot: bool, rng: &mut dyn Sampler) -> Colour {
+    let point = ShadingPoint { hit, normal: scene_object.get_normal(hit).normalised(), index: hit_index, traces_indirect };
+    let shadow_samples = get_shadow_samples(render_config, is_screenshot);
+
+    let mut total_colour = Colour::BLACK;
+
+    match render_config.light_selection {
+        LightSelection::All => {
+            for source in render_config.get_light_sampler().get_sources() {
+                total_colour = total_colour + get_source_contribution(render_config, &point, source, shadow_samples, rng);
+            }
+        },
+        LightSelection::Power => {
+            for _ in 0..shadow_samples {
+                if let Some((source, probability)) = render_config.get_light_sampler().sample_power(rng.get_1d()) {
+                    total_colour = total_colour + (get_source_contribution(render_config, &point, &source, 1, rng) / probability);
+                }
+            }
+            total_colour = total_colour / (shadow_samples as f64);
+        },
+        LightSelection::Tree => {
+            for _ in 0..shadow_samples {
+                if let Some((source, probability)) = render_config.get_light_sampler().sample_tree(&hit.position, rng.get_1d()) {
+                    total_colour = total_colour + (get_source_contribution(render_config, &point, &source, 1, rng) / probability);
+                }
+            }
+            total_colour = total_colour / (shadow_samples as f64);
+
+            for source in render_config.get_light_sampler().get_infinite_sources() {
+                total_colour = total_colour + get_source_contribution(render_config, &point, source, shadow_samples, rng);
+            }
+        },
+    }
 
+    total_colour
+}
+
+fn get_source_contribution(render_config: &RenderConfig, point: &ShadingPoint, source: &LightSource, samples: u32, rng: &mut dyn Sampler) -> Colour {
+    let (hit, normal) = (point.hit, &point.normal);
+
+    match *source {
+        LightSource::Light(i) => get_light_contribution(render_config, hit, normal, render_config.scene_lights[i].as_ref(), &[point.index], samples, rng),
+        LightSource::Object(i) => {
+            if i == point.index { return Colour::BLACK; }
+            let light = ObjectLight::new(render_config.scene_objects[i].as_ref());
+
+            // As with the environment, indirect rays that hit the object take the place of BSDF samples
+            if point.traces_indirect { return get_light_samples(render_config, hit, normal, &light, &[point.index, i], samples, rng); }
+            get_light_contribution(render_config, hit, normal, &light, &[point.index, i], samples, rng)
+        },
+        LightSource::Environment => {
+            let Some(environment) = &render_config.environment else { return Colour::BLACK; };
+            let light = EnvironmentLight::new(environment.clone(), 1.0);
+
+            // Indirect rays that miss everything take the place of BSDF samples
+            if point.traces_indirect { return get_light_samples(render_config, hit, normal, &light, &[point.index], samples, rng); }
+            get_light_contribution(render_config, hit, normal, &light, &[point.index], samples, rng)
+        },
+    }
+}
+
+fn get_light_contribution(render_config: &RenderConfig, hit: &Intersection, normal: &V3, light: &dyn Light, ignore: &[usize], samples: u32, rng: &mut dyn Sampler) -> Colour {
+    if light.is_delta() {
+        return get_sample_contribution(render_config, hit, normal, light, &light.sample(&hit.position, rng), ignore);
+    }
+
+    get_light_samples(render_config, hit, normal, light, ignore, samples, rng) + get_bsdf_samples(render_config, hit, normal, light, ignore, samples, rng)
+}
+
+// Directions chosen by the light, weighted against the chance of a BSDF sample choosing them
+fn get_light_samples(render_config: &RenderConfig, hit: &Intersection, normal: &V3, light: &dyn Light, ignore: &[usize], samples: u32, rng: &mut dyn Sampler) -> Colour {
+    let sampling = render_config.light_sampling;
     let mut total_colour = Colour::BLACK;
 
-    'light_loop: for l in &render_config.scene_lights {
-        let ray = l.get_direct_ray(&hit.position);
-        let angle_between = (normal.angle_to(&ray.line.vector) - PI).abs();
+    if sampling != LightSampling::Bsdf {
+        for _ in 0..samples {
+            let sample = light.sample(&hit.position, rng);
+            if sample.pdf <= 0.0 { continue; }
 
-        // const SMOOTHING_REGION: f64 = 0.2;
+            let mut weight = 1.0;
+            if sampling == LightSampling::MultipleImportance {
+                weight = power_heuristic(sample.pdf, get_bsdf_pdf(normal, &(sample.direction * -1.0)));
+            }
 
-        let angle_multiplier;
-        if angle_between > PI / 2.0 {
-            continue; // Light ray over 90d from hit normal
+            total_colour = total_colour + (get_sample_contribution(render_config, hit, normal, light, &sample, ignore) * weight);
         }
-        // else if angle_between > (PI / 2.0) * (1.0 - SMOOTHING_REGION)   {
-        //     let theta = angle_between - ((PI / 2.0) * (1.0 - SMOOTHING_REGION));
-        //     angle_multiplier = (1.0 - (theta / ((PI / 2.0) * SMOOTHING_REGION)));
-        // }
-        // else {
-        //     angle_multiplier = 1.0;
-        // }
+    }
 
-        angle_multiplier = 1.0 - (angle_between / (PI / 2.0));
+    total_colour / (samples as f64)
+}
 
+// Cosine weighted directions that happen to hit the light, weighted against the chance of the light choosing them
+fn get_bsdf_samples(render_config: &RenderConfig, hit: &Intersection, normal: &V3, light: &dyn Light, ignore: &[usize], samples: u32, rng: &mut dyn Sampler) -> Colour {
+    let sampling = render_config.light_sampling;
+    let mut total_colour = Colour::BLACK;
 
-        for i in 0..render_config.scene_objects.len() {
-            if i == hit_index || !render_config.scene_objects[i].get_surface_type().blocks_light { continue; }
+    if sampling != LightSampling::Light {
+        for _ in 0..samples {
+            let direction = V3::get_random_cosine(normal, rng);
+            let bsdf_pdf = get_bsdf_pdf(normal, &direction);
+
+            let sample = light.intersect(&Line::new(&hit.position, &direction).at_time(hit.sized_line.line.time));
+            if sample.is_none() { continue; }
+            let sample = sample.unwrap();
+            if sample.pdf <= 0.0 { continue; }
 
-            if Intersection::closest_bounded(
-                &render_config.scene_objects[i].get_intersections(&ray.line), 
-                0.0, ray.scale).is_some() {
-                continue 'light_loop;
+            let mut weight = 1.0;
+            if sampling == LightSampling::MultipleImportance {
+                weight = power_heuristic(bsdf_pdf, sample.pdf);
             }
+
+            let bsdf_sample = LightSample { pdf: bsdf_pdf, ..sample };
+            total_colour = total_colour + (get_sample_contribution(render_config, hit, normal, light, &bsdf_sample, ignore) * weight);
         }
+    }
+
+    total_colour / (samples as f64)
+}
+
+// Light arriving from 'sample' reflected by a white lambertian surface, divided by the pdf it was chosen with
+fn get_sample_contribution(render_config: &RenderConfig, hit: &Intersection, normal: &V3, light: &dyn Light, sample: &LightSample, ignore: &[usize]) -> Colour {
+    if sample.pdf <= 0.0 { return Colour::BLACK; }
+
+    let to_light = sample.direction * -1.0;
+    let angle_multiplier = get_angle_multiplier(normal, &to_light);
+    if angle_multiplier <= 0.0 { return Colour::BLACK; }
+
+    let transmittance = get_transmittance(render_config, &Line::new(&hit.position, &to_light).at_time(hit.sized_line.line.time), 0.0, sample.distance, ignore);
+    if transmittance.is_black() { return Colour::BLACK; }
 
-        total_colour = total_colour + (l.get_colour() * l.get_intensity(ray.length()) * angle_multiplier);
+    light.get_sample_colour(sample) * transmittance * (light.get_sample_intensity(sample) * angle_multiplier / (PI * sample.pdf))
+}
+
+// Lambert's cosine law
+fn get_angle_multiplier(normal: &V3, to_light: &V3) -> f64 {
+    normal.cos_angle_to(to_light).max(0.0)
+}
+
+// Cosine weighted hemisphere sampling of the diffuse surface
+fn get_bsdf_pdf(normal: &V3, direction: &V3) -> f64 {
+    normal.cos_angle_to(direction).max(0.0) / PI
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 { return 0.0; }
+    a / (a + b)
+}
+
+// Fraction of light passing between 'near_scale' and 'far_scale' along 'line', tinted by any transparent objects in the way
+// Every surface crossed multiplies it by 'tint * transparency', so a closed transparent object like a sphere attenuates twice - on entry and exit
+fn get_transmittance(render_config: &RenderConfig, line: &Line, near_scale: f64, far_scale: f64, ignore: &[usize]) -> Colour {
+    let mut transmittance = Colour::WHITE;
+
+    for i in 0..render_config.scene_objects.len() {
+        let scene_object = &render_config.scene_objects[i];
+        if ignore.contains(&i) || !scene_object.get_surface_type().casts_shadows() { continue; }
+
+        for hit in scene_object.get_intersections(line) {
+            if hit.sized_line.scale <= near_scale || hit.sized_line.scale >= far_scale { continue; }
+
+            // Objects made of several surfaces may only block light on some of them
+            let surface_type = scene_object.get_surface_type_at(&hit);
+            if !surface_type.casts_shadows() { continue; }
+            if surface_type.transparency <= 0.0 { return Colour::BLACK; }
+
+            // Tint by the hue of the surface without darkening by its brightness
+            let colour = scene_object.get_colour(&hit);
+            let max = colour.max_component();
+            let tint = if max > 0.0 { colour / max } else { Colour::WHITE };
+
+            transmittance = transmittance * tint * surface_type.transparency;
+        }
     }
 
-    total_colour
+    transmittance
 }
 
 #[allow(dead_code)]
@@ -98,13 +436,15 @@ fn general_light_falloff(distance: f64) -> f64 {
     1.0 / (distance.powi(2) * 4.0 * PI)
 }
 
-fn get_indirect_light(render_config: &RenderConfig, hit: &Intersection, scene_object: &Box<dyn Object + Sync>, hit_index: usize, ray_count: u32, rng: &mut ThreadRng) -> Colour {
+fn get_indirect_light(render_config: &RenderConfig, hit: &Intersection, scene_object: &Box<dyn Object + Sync + Send>, hit_index: usize, ray_count: u32, is_screenshot: bool, rng: &mut dyn Sampler) -> Colour {
     let normal = scene_object.get_normal(hit);
 
     let mut total_colour = Colour::BLACK;
 
     for _ in 0..ray_count {
-        let ray = Line::new(&hit.position, &V3::get_random(&normal, 0.5 * PI, rng));
+        // Cosine weighted, so the average of the light found is the light reflected by a diffuse surface
+        let direction = V3::get_random_cosine(&normal, rng);
+        let ray = Line::new(&hit.position, &direction).at_time(hit.sized_line.line.time);
 
         let mut closest_dist = f64::INFINITY;
         let mut closest_object = None;
@@ -117,7 +457,7 @@ fn get_indirect_light(render_config: &RenderConfig, hit: &Intersection, scene_ob
             match Intersection::closest_bounded(&render_config.scene_objects[i].get_intersections(&ray), 0.0, f64::INFINITY) {
                 None => {},
                 Some(hit) => {
-                    if hit.sized_line.scale <= closest_dist { 
+                    if hit.sized_line.scale <= closest_dist && render_config.scene_objects[i].get_surface_type_at(hit).blocks_light { 
                         closest_dist = hit.sized_line.scale;
                         closest_hit = Some(hit.clone());
                         closest_object = Some(&render_config.scene_objects[i]);
@@ -127,17 +467,31 @@ fn get_indirect_light(render_config: &RenderConfig, hit: &Intersection, scene_ob
             }
         }
 
-        if closest_hit.is_none() { continue; }
+        if closest_hit.is_none() {
+            if let Some(environment) = &render_config.environment {
+                let weight = get_bounce_light_weight(render_config, &normal, &direction, environment.get_pdf(&direction), is_screenshot);
+                total_colour = total_colour + (environment.get_colour(&direction) * weight);
+            }
+            continue;
+        }
 
         let closest_hit = closest_hit.unwrap();
         let closest_object = closest_object.unwrap();
-        let surface_type = closest_object.get_surface_type();
+        let surface_type = closest_object.get_surface_type_at(&closest_hit);
+
+        if surface_type.is_emissive() {
+            let light = ObjectLight::new(closest_object.as_ref());
+            if let Some(sample) = light.intersect(&ray) {
+                let weight = get_bounce_light_weight(render_config, &normal, &direction, sample.pdf, is_screenshot);
+                total_colour = total_colour + (light.get_sample_colour(&sample) * weight);
+            }
+        }
 
         if surface_type.opaqueness == 0.0 || surface_type.diffuseness == 0.0 { continue; }
 
         // TODO: Light falloff between 'hit' and 'closest hit'
         let current_colour = 
-            get_direct_light(render_config, &closest_hit, &render_config.scene_objects[closest_hit_index.unwrap()], closest_hit_index.unwrap());
+            get_direct_light(render_config, &closest_hit, &render_config.scene_objects[closest_hit_index.unwrap()], closest_hit_index.unwrap(), false, is_screenshot, rng);
 
         total_colour = total_colour + (closest_object.get_colour(&closest_hit) * current_colour * surface_type.diffuseness * surface_type.opaqueness);
     }
@@ -145,8 +499,19 @@ fn get_indirect_light(render_config: &RenderConfig, hit: &Intersection, scene_ob
     total_colour / (ray_count as f64)
 }
 
+// Share of the environment or an emissive object an indirect ray should add when it finds them, the rest coming from sampling them as lights
+fn get_bounce_light_weight(render_config: &RenderConfig, normal: &V3, direction: &V3, light_pdf: f64, is_screenshot: bool) -> f64 {
+    if !render_config.get_direct_lighting(is_screenshot) { return 1.0; }
+
+    match render_config.light_sampling {
+        LightSampling::Light => 0.0,
+        LightSampling::Bsdf => 1.0,
+        LightSampling::MultipleImportance => power_heuristic(get_bsdf_pdf(normal, direction), light_pdf),
+    }
+}
+
 fn get_colour_recursively(ray: Line, render_config: &RenderConfig, 
-    is_screenshot: bool, reflection_depth_remaining: u32, rng: &mut ThreadRng) -> Colour {
+    is_screenshot: bool, reflection_depth_remaining: u32, rng: &mut dyn Sampler) -> Colour {
     let mut closest_dist = f64::INFINITY;
     let mut closest_object = None;
     let mut closest_hit = None;
@@ -167,6 +532,10 @@ fn get_colour_recursively(ray: Line, render_config: &RenderConfig,
     }
 
     return if !closest_hit.is_some() {
+        if let Some(environment) = &render_config.environment {
+            return environment.get_colour(&ray.vector);
+        }
+
         let offset;
         if ray.vector.y == 0.0 { offset = 0.00001; } else { offset = 0.0; }
 
@@ -183,7 +552,7 @@ fn get_colour_recursively(ray: Line, render_config: &RenderConfig,
         }
 
         let mut new_colour = Colour::BLACK;
-        let object_surface_properties = scene_object.get_surface_type();
+        let object_surface_properties = scene_object.get_surface_type_at(&closest_hit);
 
         if object_surface_properties.opaqueness != 0.0 {
             let mut light_colour = Colour::WHITE;
@@ -192,30 +561,27 @@ fn get_colour_recursively(ray: Line, render_config: &RenderConfig,
                 (!render_config.screenshot_enable_full_bright && is_screenshot)) {
                 light_colour = Colour::BLACK;
 
-                if (render_config.enable_direct_lighting && !is_screenshot) || 
-                    (render_config.screenshot_enable_direct_lighting && is_screenshot) {
-                        light_colour = light_colour + get_direct_light(render_config, &closest_hit, scene_object, closest_hit_index.unwrap());
+                let indirect_ray_count = if object_surface_properties.diffuseness != 0.0 { render_config.get_indirect_ray_count(is_screenshot) } else { 0 };
+
+                if render_config.get_direct_lighting(is_screenshot) {
+                    light_colour = light_colour + get_direct_light(render_config, &closest_hit, scene_object, closest_hit_index.unwrap(), indirect_ray_count != 0, is_screenshot, rng);
                 }
 
-                if object_surface_properties.diffuseness != 0.0 {
-                    if render_config.indirect_lighting_ray_count != 0 && !is_screenshot {
-                        light_colour = light_colour + 
-                            (get_indirect_light(render_config, &closest_hit, scene_object, closest_hit_index.unwrap(), render_config.indirect_lighting_ray_count, rng)
-                            * object_surface_properties.diffuseness);
-                    }
-                    else if render_config.screenshot_indirect_lighting_ray_count != 0 && is_screenshot {
-                        light_colour = light_colour + 
-                            (get_indirect_light(render_config, &closest_hit, scene_object, closest_hit_index.unwrap(), render_config.screenshot_indirect_lighting_ray_count, rng)
-                            * object_surface_properties.diffuseness);
-                    }
+                if indirect_ray_count != 0 {
+                    light_colour = light_colour + 
+                        (get_indirect_light(render_config, &closest_hit, scene_object, closest_hit_index.unwrap(), indirect_ray_count, is_screenshot, rng)
+                        * object_surface_properties.diffuseness);
                 }
             }
 
             new_colour = (scene_object.get_colour(&closest_hit) * (light_colour + render_config.global_light)).proportionally_limited() * object_surface_properties.opaqueness;
         }
+        if object_surface_properties.is_emissive() {
+            new_colour = new_colour + object_surface_properties.emission;
+        }
         if object_surface_properties.reflectiveness != 0.0 { 
             new_colour = new_colour +
-            (get_colour_recursively(scene_object.get_reflection_line(&ray, &closest_hit), 
+            (get_colour_recursively(scene_object.get_reflection_line(&ray, &closest_hit).at_time(ray.time), 
                 render_config,
                 is_screenshot,
                 reflection_depth_remaining - 1,
@@ -224,7 +590,7 @@ fn get_colour_recursively(ray: Line, render_config: &RenderConfig,
         }
         if object_surface_properties.transparency != 0.0 { 
             new_colour = new_colour + 
-            (get_colour_recursively(scene_object.get_transparent_line(&ray, &closest_hit), 
+            (get_colour_recursively(scene_object.get_transparent_line(&ray, &closest_hit).at_time(ray.time), 
                 render_config, 
                 is_screenshot,
                 reflection_depth_remaining - 1
@@ -233,4 +599,287 @@ fn get_colour_recursively(ray: Line, render_config: &RenderConfig,
 
         new_colour
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    use crate::colour::{Colour, colour_getters::SolidColour};
+    use crate::lights::{DirectionalLight, RectLight};
+    use crate::maths::{Intersection, lines::Line, vectors::V3, sampler::{IndependentSampler, SamplerType}};
+    use crate::objects::{Object, Plane, Sphere, SurfaceType};
+    use crate::environment::Environment;
+
+    use super::{RenderConfig, LightSampling, Denoiser, FeatureBuffers, Camera, RenderTarget, CancellationToken, CheckpointHeader, get_direct_light, get_indirect_light, get_transmittance, power_heuristic};
+
+    struct WhiteEnvironment;
+
+    impl Environment for WhiteEnvironment {
+        fn get_colour(&self, _direction: &V3) -> Colour { Colour::WHITE }
+    }
+
+    fn get_floor() -> Box<dyn Object + Sync + Send> {
+        Box::new(Plane::new(&V3::ZERO, &V3::FORWARD, &V3::RIGHT, None,
+            Box::new(SolidColour { colour: Colour::WHITE }),
+            SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false)))
+    }
+
+    fn get_floor_hit() -> Intersection {
+        Intersection::new(&Line::new(&V3::UP, &V3::DOWN), 1.0, &V3::ZERO)
+    }
+
+    // Floor under a panel light, seen from the side in a small screenshot
+    fn get_lit_floor(resolution: (u32, u32)) -> (RenderConfig, Camera) {
+        let mut render_config = RenderConfig::for_tests(vec![get_floor()],
+            vec![Box::new(RectLight::new(&V3::new(0.0, 2.0, 0.0), &V3::FORWARD, &V3::RIGHT, &Colour::WHITE, 4.0))]);
+        render_config.screenshot_resolution = resolution;
+        render_config.screenshot_max_reflection = 1;
+        (render_config, Camera::look_at(&V3::new(-3.0, 2.0, 0.0), &V3::ZERO, &V3::UP, 60.0))
+    }
+
+    #[test]
+    fn directional_light_follows_lambert_cosine() {
+        for degrees in [0.0, 30.0, 45.0, 60.0, 89.0] {
+            let theta: f64 = degrees * PI / 180.0;
+            let render_config = RenderConfig::for_tests(vec![get_floor()],
+                vec![Box::new(DirectionalLight::new(&V3::new(theta.sin(), -theta.cos(), 0.0), &Colour::WHITE, 2.0))]);
+
+            let light = get_direct_light(&render_config, &get_floor_hit(), &render_config.scene_objects[0], 0, false, false, &mut IndependentSampler::new(0));
+
+            // Irradiance of 2cos reflected by a white lambertian surface
+            assert!((light.luminance() - 2.0 * theta.cos() / PI).abs() < 1e-9, "{} degrees: {:?}", degrees, light);
+        }
+    }
+
+    #[test]
+    fn uniform_environment_lights_a_floor_the_same_directly_and_indirectly() {
+        let mut render_config = RenderConfig::for_tests(vec![get_floor()], Vec::new());
+        render_config.environment = Some(Arc::new(WhiteEnvironment));
+        render_config.shadow_samples = 4096;
+        let (hit, floor, mut rng) = (get_floor_hit(), &render_config.scene_objects[0], IndependentSampler::new(0));
+
+        let direct = get_direct_light(&render_config, &hit, floor, 0, false, false, &mut rng);
+        let shared = get_direct_light(&render_config, &hit, floor, 0, true, false, &mut rng) + get_indirect_light(&render_config, &hit, floor, 0, 4096, false, &mut rng);
+
+        let mut render_config = RenderConfig::for_tests(vec![get_floor()], Vec::new());
+        render_config.environment = Some(Arc::new(WhiteEnvironment));
+        render_config.enable_direct_lighting = false;
+        let indirect = get_indirect_light(&render_config, &hit, &render_config.scene_objects[0], 0, 4096, false, &mut rng);
+
+        // A white floor under a sky of radiance 1 reflects radiance 1, however the sky is sampled
+        for light in [direct, shared, indirect] {
+            assert!((light.luminance() - 1.0).abs() < 0.05, "{:?} {:?} {:?}", direct, shared, indirect);
+        }
+    }
+
+    #[test]
+    fn emissive_panel_lights_a_floor_the_same_directly_and_indirectly() {
+        let get_render_config = |light_sampling: LightSampling, enable_direct_lighting: bool| {
+            let panel: Box<dyn Object + Sync + Send> = Box::new(Plane::new(&V3::UP, &V3::FORWARD, &V3::RIGHT, Some((-2.0, -2.0, 2.0, 2.0)),
+                Box::new(SolidColour { colour: Colour::WHITE }),
+                SurfaceType::new_emissive(&Colour::WHITE, 1.0)));
+            let mut render_config = RenderConfig::for_tests(vec![get_floor(), panel], Vec::new());
+            render_config.light_sampling = light_sampling;
+            render_config.enable_direct_lighting = enable_direct_lighting;
+            render_config.shadow_samples = 4096;
+            render_config
+        };
+        let (hit, mut rng) = (get_floor_hit(), IndependentSampler::new(0));
+
+        let render_config = get_render_config(LightSampling::MultipleImportance, true);
+        let direct = get_direct_light(&render_config, &hit, &render_config.scene_objects[0], 0, false, false, &mut rng);
+
+        let mut shared = Vec::new();
+        for light_sampling in [LightSampling::Light, LightSampling::Bsdf, LightSampling::MultipleImportance] {
+            let render_config = get_render_config(light_sampling, true);
+            let floor = &render_config.scene_objects[0];
+            shared.push(get_direct_light(&render_config, &hit, floor, 0, true, false, &mut rng) + get_indirect_light(&render_config, &hit, floor, 0, 4096, false, &mut rng));
+        }
+
+        let render_config = get_render_config(LightSampling::MultipleImportance, false);
+        let indirect = get_indirect_light(&render_config, &hit, &render_config.scene_objects[0], 0, 4096, false, &mut rng);
+
+        // However the panel's light is split between direct lighting and bounces, it's only counted once
+        for light in shared.iter().chain([&indirect]) {
+            assert!((light.luminance() - direct.luminance()).abs() < 0.05 * direct.luminance(), "{:?} {:?} {:?}", direct, shared, indirect);
+        }
+    }
+
+    #[test]
+    fn directional_light_below_surface_gives_no_light() {
+        let render_config = RenderConfig::for_tests(vec![get_floor()],
+            vec![Box::new(DirectionalLight::new(&V3::UP, &Colour::WHITE, 2.0))]);
+
+        let light = get_direct_light(&render_config, &get_floor_hit(), &render_config.scene_objects[0], 0, false, false, &mut IndependentSampler::new(0));
+
+        assert!(light.is_black());
+    }
+
+    #[test]
+    fn glass_tints_shadows_without_blocking_light() {
+        let glass = SurfaceType::new(0.0, 0.0, 0.5, 1.5, false, false);
+        let sphere: Box<dyn Object + Sync + Send> = Box::new(Sphere::new(V3::new(0.0, 5.0, 0.0), 1.0, Colour::from_f64(1.0, 0.5, 0.5), glass));
+        let render_config = RenderConfig::for_tests(vec![get_floor(), sphere],
+            vec![Box::new(DirectionalLight::new(&V3::DOWN, &Colour::WHITE, 2.0))]);
+
+        let light = get_direct_light(&render_config, &get_floor_hit(), &render_config.scene_objects[0], 0, false, false, &mut IndependentSampler::new(0));
+        let transmittance = get_transmittance(&render_config, &Line::new(&V3::ZERO, &V3::UP), 0.0, f64::INFINITY, &[0]);
+
+        // Tinted and halved on the way into the sphere and again on the way out
+        let expected = Colour::from_f64(1.0, 0.25, 0.25) * 0.25;
+        assert!(transmittance.distance(&expected) < 1e-9, "{:?}", transmittance);
+        assert!((light.luminance() - expected.luminance() * 2.0 / PI).abs() < 1e-9, "{:?}", light);
+    }
+
+    #[test]
+    fn directional_light_is_blocked_at_any_distance() {
+        let occluder: Box<dyn Object + Sync + Send> = Box::new(Plane::new(&V3::new(0.0, 1e9, 0.0), &V3::FORWARD, &V3::RIGHT, Some((-1.0, -1.0, 1.0, 1.0)),
+            Box::new(SolidColour { colour: Colour::WHITE }),
+            SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false)));
+        let render_config = RenderConfig::for_tests(vec![get_floor(), occluder],
+            vec![Box::new(DirectionalLight::new(&V3::DOWN, &Colour::WHITE, 2.0))]);
+
+        let light = get_direct_light(&render_config, &get_floor_hit(), &render_config.scene_objects[0], 0, false, false, &mut IndependentSampler::new(0));
+
+        assert!(light.is_black());
+    }
+
+    #[test]
+    fn light_bsdf_and_multiple_importance_sampling_agree() {
+        let results: Vec<f64> = [LightSampling::Light, LightSampling::Bsdf, LightSampling::MultipleImportance].into_iter().map(|sampling| {
+            let mut render_config = RenderConfig::for_tests(vec![get_floor()],
+                vec![Box::new(RectLight::new(&V3::new(0.5, 1.0, 0.0), &(V3::FORWARD * 2.0), &(V3::RIGHT * 2.0), &Colour::WHITE, 4.0))]);
+            render_config.light_sampling = sampling;
+            render_config.shadow_samples = 20000;
+
+            get_direct_light(&render_config, &get_floor_hit(), &render_config.scene_objects[0], 0, false, false, &mut IndependentSampler::new(0)).luminance()
+        }).collect();
+
+        assert!(results[2] > 0.0);
+        for result in &results {
+            assert!((result - results[2]).abs() < 0.03 * results[2], "{:?}", results);
+        }
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        for (pdf, other_pdf) in [(0.1, 0.1), (1.0, 3.0), (50.0, 0.01), (0.0, 2.0)] {
+            assert!((power_heuristic(pdf, other_pdf) + power_heuristic(other_pdf, pdf) - 1.0).abs() < 1e-12);
+        }
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn seeded_render_is_identical_across_threads() {
+        let (mut render_config, camera) = get_lit_floor((16, 12));
+        render_config.screenshot_pixel_samples = 4;
+        render_config.screenshot_shadow_samples = 2;
+        render_config.seed = 7;
+
+        let single_threaded = camera.get_image(&render_config, true, false);
+
+        assert_eq!(single_threaded, camera.get_image_threaded(&render_config));
+        assert_eq!(single_threaded, camera.get_image_threaded(&render_config));
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_early_on_flat_pixels() {
+        let mut render_config = RenderConfig::for_tests(vec![], vec![]);
+        render_config.screenshot_resolution = (4, 4);
+        render_config.screenshot_pixel_samples = 64;
+        render_config.adaptive_threshold = Some(0.01);
+        render_config.adaptive_min_samples = 8;
+        let camera = Camera::look_at(&V3::ZERO, &V3::FORWARD, &V3::UP, 60.0);
+
+        let (_, sample_counts) = camera.get_image_threaded_with_samples(&render_config);
+
+        assert!(sample_counts.iter().all(|s| *s == 8), "{:?}", sample_counts);
+    }
+
+    #[test]
+    fn denoiser_smooths_noise_on_a_flat_surface() {
+        let resolution = (16, 16);
+        let count = (resolution.0 * resolution.1) as usize;
+        let features = FeatureBuffers { albedo: vec![Colour::WHITE; count], normal: vec![V3::UP; count], depth: vec![1.0; count] };
+        let noisy: Vec<Colour> = (0..count).map(|i| if (i + i / 16) % 2 == 0 { Colour::from_f64(0.4, 0.4, 0.4) } else { Colour::from_f64(0.6, 0.6, 0.6) }).collect();
+
+        let denoised = Denoiser::new(3).denoise(&noisy, &features, resolution);
+
+        let spread = |colours: &[Colour]| {
+            let luminances = colours.iter().map(|c| c.luminance());
+            luminances.clone().fold(f64::MIN, f64::max) - luminances.fold(f64::MAX, f64::min)
+        };
+        assert!(spread(&denoised) < spread(&noisy) / 2.0, "{:?}", &denoised[..16]);
+    }
+
+    #[test]
+    fn denoiser_spreads_light_brighter_than_white_before_clamping() {
+        let resolution = (16, 16);
+        let count = (resolution.0 * resolution.1) as usize;
+        let features = FeatureBuffers { albedo: vec![Colour::WHITE; count], normal: vec![V3::UP; count], depth: vec![1.0; count] };
+
+        // One pixel in four caught a bright light, averaging 0.75 - clamped first it would average 0.25
+        let mut denoiser = Denoiser::new(3);
+        denoiser.colour_sigma = f64::INFINITY;
+        let noisy: Vec<Colour> = (0..count).map(|i| if i % 2 == 0 && (i / 16) % 2 == 0 { Colour::from_f64(3.0, 3.0, 3.0) } else { Colour::BLACK }).collect();
+        let denoised = denoiser.denoise(&noisy, &features, resolution);
+
+        let centre = denoised[8 * 16 + 8].luminance();
+        assert!((centre - 0.75).abs() < 0.1, "{}", centre);
+    }
+
+    #[test]
+    fn cancelled_tile_render_samples_nothing() {
+        let (render_config, camera) = get_lit_floor((256, 256));
+        let target = RenderTarget::new(render_config.screenshot_resolution);
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let mut completed_tiles = 0;
+
+        let completed = camera.render_tiles(&render_config, true, &target, &mut |progress| completed_tiles = progress.completed_tiles, &cancellation);
+
+        assert!(!completed);
+        assert_eq!(completed_tiles, 0);
+        assert!(target.get_sample_counts().iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn resumed_checkpoint_matches_uninterrupted_render() {
+        let (mut render_config, camera) = get_lit_floor((80, 60));
+        render_config.screenshot_pixel_samples = 4;
+        render_config.seed = 3;
+        let header = CheckpointHeader::new(&camera, &render_config);
+
+        // Stopped after the first tile of a 4 spp render, leaving the rest unrendered
+        let path = std::env::temp_dir().join("resumed_checkpoint_matches_uninterrupted_render.checkpoint");
+        let path = path.to_str().unwrap();
+        let partial = RenderTarget::new(render_config.screenshot_resolution);
+        let cancellation = CancellationToken::new();
+        assert!(!camera.render_tiles(&render_config, true, &partial, &mut |_| cancellation.cancel(), &cancellation));
+        partial.save_checkpoint(path, &header).unwrap();
+
+        let saved_header = CheckpointHeader::load(path).unwrap();
+        assert!(saved_header.check(&header).is_ok(), "{:?}", saved_header);
+
+        // Then finished at 8 spp
+        render_config.screenshot_pixel_samples = 8;
+        let uninterrupted = RenderTarget::new(render_config.screenshot_resolution);
+        camera.render_tiles(&render_config, true, &uninterrupted, &mut |_| {}, &CancellationToken::new());
+
+        let resumed = RenderTarget::new(render_config.screenshot_resolution);
+        let moved_camera = Camera::look_at(&V3::new(-3.0, 2.5, 0.0), &V3::ZERO, &V3::UP, 60.0);
+        assert!(resumed.load_checkpoint(path, &CheckpointHeader { seed: 4, ..header.clone() }).is_err());
+        assert!(resumed.load_checkpoint(path, &CheckpointHeader { sample_target: 2, ..header.clone() }).is_err());
+        let stratified = CheckpointHeader { sampler: SamplerType::Stratified, ..header.clone() };
+        assert!(stratified.check(&CheckpointHeader { sample_target: 8, ..stratified.clone() }).is_err());
+        assert!(resumed.load_checkpoint(path, &CheckpointHeader::new(&moved_camera, &render_config)).is_err());
+        resumed.load_checkpoint(path, &CheckpointHeader::new(&saved_header.camera, &render_config)).unwrap();
+        saved_header.camera.render_tiles(&render_config, true, &resumed, &mut |_| {}, &CancellationToken::new());
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(uninterrupted.get_sample_counts(), resumed.get_sample_counts());
+        assert_eq!(uninterrupted.get_pixel_data(), resumed.get_pixel_data());
+    }
+}
@@ -1,28 +1,253 @@
-use std::cell::RefCell;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, mpsc::{self, RecvTimeoutError}, atomic::{AtomicUsize, Ordering}};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+use std::f64::consts::PI;
 
-use rand::{rngs::ThreadRng, thread_rng};
 use rayon::prelude::*;
 use thread_local::ThreadLocal;
 
-use crate::maths::{vectors::V3, lines::Line};
+use crate::maths::{vectors::V3, lines::Line, quaternion::Quaternion, sampler::Sampler};
+use crate::colour::Colour;
+use crate::animation::Keyframe;
 
-use super::RenderConfig;
+use super::{RenderConfig, Projection, FeatureBuffers, Tile, RenderTarget, RenderProgress, CancellationToken, PixelState};
 
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub position: V3,
-    pub rotation: (f64, f64), // Y, Z
-    pub fov: f64,
+    pub orientation: Quaternion,
+    pub fov: f64, // Vertical field of view in degrees
+    pub aperture_radius: f64, // 0 for a pinhole camera
+    pub focus_distance: f64,
+    pub aperture_blades: u32, // 0 for a circular aperture
+    pub aperture_rotation: f64,
+    pub projection: Projection,
+    pub shutter: (f64, f64), // Open and close times in seconds after the frame's time, equal for no motion blur
+    pub motion: Option<(V3, Quaternion)>, // Position and orientation when the shutter closes, if the camera is moving
+}
+
+// A pixel to be sampled, and the image it belongs to
+#[derive(Clone, Copy)]
+struct ImagePixel {
+    x: u32,
+    y: u32,
+    resolution: (u32, u32),
+    is_screenshot: bool,
 }
 
 #[allow(dead_code)]
 impl Camera {
-    pub fn new(position: &V3, rotation: (f64, f64), fov: f64) -> Self {
-        Self { position: position.clone(), rotation, fov }
+    pub fn new(position: &V3, orientation: Quaternion, fov: f64) -> Self {
+        Self { position: position.clone(), orientation: orientation.normalised(), fov, aperture_radius: 0.0, focus_distance: 10.0, aperture_blades: 0, aperture_rotation: 0.0, projection: Projection::Perspective, shutter: (0.0, 0.0), motion: None }
+    }
+
+    pub fn look_at(position: &V3, target: &V3, up: &V3, fov: f64) -> Self {
+        Self::new(position, Quaternion::look_along(&(target - position), up), fov)
+    }
+
+    // Turn about the world up axis so the horizon stays level
+    pub fn rotate_yaw(&mut self, radians: f64) {
+        self.orientation = (Quaternion::from_axis_angle(&V3::UP, radians) * self.orientation).normalised();
+    }
+
+    // Tilt about the camera's own right axis, refusing to pass straight up or down
+    // Going over the top would turn the camera upside down, so up has to stay above the horizon
+    pub fn rotate_pitch(&mut self, radians: f64) {
+        let orientation = (self.orientation * Quaternion::from_axis_angle(&V3::RIGHT, radians)).normalised();
+        let angle = orientation.get_forward().angle_to(&V3::UP);
+        if angle > 0.001 && angle < PI - 0.001 && orientation.get_up().y > 0.0 { self.orientation = orientation; }
+    }
+
+    pub fn rotate_roll(&mut self, radians: f64) {
+        self.orientation = (self.orientation * Quaternion::from_axis_angle(&V3::FORWARD, radians)).normalised();
+    }
+
+    // Heading about the world up axis, used to keep movement horizontal
+    pub fn get_yaw(&self) -> f64 {
+        let forward = self.orientation.get_forward();
+        let mut yaw = (-forward.z).atan2(forward.x);
+        if forward.x.abs() < 1e-9 && forward.z.abs() < 1e-9 {
+            let up = self.orientation.get_up() * forward.y.signum();
+            yaw = up.z.atan2(-up.x);
+        }
+        yaw
+    }
+
+    pub fn with_aperture(mut self, aperture_radius: f64, focus_distance: f64) -> Self {
+        self.aperture_radius = aperture_radius;
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    pub fn with_blades(mut self, aperture_blades: u32, aperture_rotation: f64) -> Self {
+        self.aperture_blades = aperture_blades;
+        self.aperture_rotation = aperture_rotation;
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter = (open, close.max(open));
+        self
+    }
+
+    // Moves the camera from its current pose to 'position' and 'orientation' while the shutter is open
+    pub fn with_motion(mut self, position: &V3, orientation: Quaternion) -> Self {
+        self.motion = Some((position.clone(), orientation.normalised()));
+        self
+    }
+
+    // Random time while the shutter is open
+    fn get_shutter_time(&self, rng: &mut dyn Sampler) -> f64 {
+        if self.shutter.1 <= self.shutter.0 { return self.shutter.0; }
+        rng.get_range(self.shutter.0..self.shutter.1)
+    }
+
+    // Position and orientation at 'time' within the shutter interval
+    fn get_pose(&self, time: f64) -> (V3, Quaternion) {
+        match self.motion {
+            Some((position, orientation)) if self.shutter.1 > self.shutter.0 => {
+                let t = (time - self.shutter.0) / (self.shutter.1 - self.shutter.0);
+                (self.position.lerp(&position, t), self.orientation.lerp(&orientation, t))
+            },
+            _ => (self.position, self.orientation),
+        }
+    }
+
+    pub fn get_centre_ray(&self) -> Line {
+        Line::new(&self.position, &self.orientation.get_forward())
+    }
+
+    // Point on the aperture in camera space - uniform over a disc or a regular polygon
+    fn get_lens_point(&self, rng: &mut dyn Sampler) -> V3 {
+        if self.aperture_blades < 3 {
+            let (u, v) = rng.get_2d();
+            let r = self.aperture_radius * u.sqrt();
+            let theta = (2.0 * v - 1.0) * PI;
+            return V3::new(0.0, r * theta.sin(), r * theta.cos());
+        }
+
+        // Pick one of the triangles making up the polygon then a point within it
+        let segment = (2.0 * PI) / self.aperture_blades as f64;
+        let start = self.aperture_rotation + segment * (rng.get_index(self.aperture_blades) as f64);
+        let (mut a, mut b): (f64, f64) = rng.get_2d();
+        if a + b > 1.0 { a = 1.0 - a; b = 1.0 - b; }
+
+        let y = self.aperture_radius * (a * start.sin() + b * (start + segment).sin());
+        let z = self.aperture_radius * (a * start.cos() + b * (start + segment).cos());
+        V3::new(0.0, y, z)
+    }
+
+    // Turns a camera space ray into a world space ray, through the lens if it has an aperture
+    fn get_lens_ray(&self, ray_origin: &V3, ray_vector: &V3, rng: &mut dyn Sampler) -> Line {
+        let time = self.get_shutter_time(rng);
+        let (position, orientation) = self.get_pose(time);
+
+        let mut origin = ray_origin.clone();
+        let mut vector = ray_vector.clone();
+
+        if self.aperture_radius > 0.0 {
+            let focus_point = if self.projection.is_planar() { ray_origin + ray_vector * (self.focus_distance / ray_vector.x) }
+            else { ray_origin + ray_vector.normalised() * self.focus_distance };
+            origin = ray_origin + self.get_lens_point(rng);
+            vector = focus_point - origin;
+        }
+
+        Line::new(&(position + orientation.rotate(&origin)), &orientation.rotate(&vector)).at_time(time)
+    }
+
+    // World space ray through a position on the image in pixels
+    pub fn get_ray(&self, position: (f64, f64), resolution: (u32, u32), rng: &mut dyn Sampler) -> Option<Line> {
+        let (origin, vector) = self.projection.get_ray(position, resolution, self.fov.to_radians())?;
+        Some(self.get_lens_ray(&origin, &vector, rng))
+    }
+
+    // World space ray through a position on the image ignoring the aperture
+    pub fn get_pinhole_ray(&self, position: (f64, f64), resolution: (u32, u32)) -> Option<Line> {
+        let (origin, vector) = self.projection.get_ray(position, resolution, self.fov.to_radians())?;
+        Some(Line::new(&(self.position + self.orientation.rotate(&origin)), &self.orientation.rotate(&vector)).at_time(self.shutter.0))
+    }
+
+    // First hit albedo, normal and depth through the centre of every pixel, for the denoiser
+    pub fn get_feature_buffers(&self, render_config: &RenderConfig, is_screenshot: bool) -> FeatureBuffers {
+        let resolution = if is_screenshot { render_config.screenshot_resolution } else { render_config.resolution };
+
+        let features: Vec<(Colour, V3, f64)> = (0..(resolution.0 * resolution.1)).into_par_iter().map(|i| {
+            let centre = ((i % resolution.0) as f64 + 0.5, (i / resolution.0) as f64 + 0.5);
+            match self.get_pinhole_ray(centre, resolution) {
+                Some(ray) => super::get_features(&ray, render_config),
+                None => (Colour::BLACK, V3::ZERO, f64::MAX),
+            }
+        }).collect();
+
+        FeatureBuffers {
+            albedo: features.iter().map(|f| f.0).collect(),
+            normal: features.iter().map(|f| f.1).collect(),
+            depth: features.iter().map(|f| f.2).collect(),
+        }
+    }
+
+    // Filtered average of samples spread over the filter's footprint around the pixel centre, and the number of samples taken
+    fn get_pixel_colour(&self, x: u32, y: u32, resolution: (u32, u32), render_config: &RenderConfig, rng: &mut dyn Sampler, is_screenshot: bool) -> (Colour, u32) {
+        let mut pixel = PixelState::default();
+        self.accumulate_pixel(&ImagePixel { x, y, resolution, is_screenshot }, render_config, rng, &mut pixel);
+        (pixel.get_colour(), pixel.samples)
+    }
+
+    // Adds samples to 'pixel' until it has the configured number, or it converges for adaptive screenshots
+    // Sample indices carry on from those already taken, so a resumed pixel matches one rendered in one go
+    fn accumulate_pixel(&self, image_pixel: &ImagePixel, render_config: &RenderConfig, rng: &mut dyn Sampler, pixel: &mut PixelState) {
+        let ImagePixel { x, y, resolution, is_screenshot } = *image_pixel;
+        let centre = (x as f64 + 0.5, y as f64 + 0.5);
+        let samples = render_config.get_pixel_samples(is_screenshot);
+
+        if samples == 1 {
+            if pixel.samples > 0 { return; }
+
+            rng.start_sample((x, y), 0);
+            let colour = match self.get_ray(centre, resolution, rng) {
+                Some(ray) => super::get_colour(ray, render_config, rng, is_screenshot),
+                None => Colour::BLACK,
+            };
+            pixel.add_sample(&colour, 1.0);
+            return;
+        }
+
+        let threshold = if is_screenshot { render_config.adaptive_threshold } else { None };
+        let min_samples = render_config.adaptive_min_samples.clamp(2, samples);
+
+        let radius = render_config.filter.get_radius().max(0.5);
+
+        while pixel.samples < samples {
+            // Adaptive screenshots stop early once the pixel has converged
+            if let Some(threshold) = threshold {
+                if pixel.samples >= min_samples && pixel.get_standard_error().is_some_and(|e| e <= threshold) { return; }
+            }
+
+            rng.start_sample((x, y), pixel.samples);
+
+            let (u, v) = rng.get_2d();
+            let offset = (radius * (2.0 * u - 1.0), radius * (2.0 * v - 1.0));
+
+            let weight = render_config.filter.get_weight(offset);
+            if weight == 0.0 {
+                pixel.samples += 1;
+                continue;
+            }
+
+            let colour = match self.get_ray((centre.0 + offset.0, centre.1 + offset.1), resolution, rng) {
+                Some(ray) => super::get_colour(ray, render_config, rng, is_screenshot),
+                None => Colour::BLACK,
+            };
+
+            pixel.add_sample(&colour, weight);
+        }
     }
 
     pub fn get_image_threaded_old(&self, render_config: &RenderConfig) -> Vec<u8> {
@@ -60,65 +285,70 @@ impl Camera {
     }
 
     pub fn get_image_threaded(&self, render_config: &RenderConfig) -> Vec<u8> {
-        let resolution;
-        let is_screenshot = true;
-        if is_screenshot { resolution = render_config.screenshot_resolution }
-        else { resolution = render_config.resolution; }
-
-        thread_local!(static STORE: RefCell<Option<ThreadRng>> = RefCell::new(None));
-
-        const PIXEL_GROUP: usize = 100;
-
-        let data: Vec<Vec<(u8, u8, u8)>> = (0..(resolution.0 * resolution.1)).into_par_iter().chunks(PIXEL_GROUP).map(
-            |is| {
-
-            STORE.with(|cell| {
-                let mut local_store = cell.borrow_mut();
-                if local_store.is_none() {
-                    *local_store = Some(thread_rng());
-                }
-
-                let rng = local_store.as_mut().unwrap();
-
-                let mut result = Vec::with_capacity(PIXEL_GROUP);
-                for i in is {
-                    let x = i % resolution.0;
-                    let y = i / resolution.0;
+        self.get_image_threaded_with_samples(render_config).0
+    }
 
-                    let ray_vector = (V3::FORWARD * self.fov) +
-                        V3::new(0.0,
-                                -(((y as i32) - ((resolution.1) / 2) as i32) as f64) /
-                                    ((resolution.1 as f64) / (2.0)),
-                                (((x as i32) - ((resolution.0) / 2) as i32) as f64) /
-                                    ((resolution.0 as f64) / ((resolution.0 as f64 / resolution.1 as f64) * 2.0))
-                        );
+    // Screenshot pixel data along with the number of samples spent on each pixel
+    pub fn get_image_threaded_with_samples(&self, render_config: &RenderConfig) -> (Vec<u8>, Vec<u32>) {
+        let target = RenderTarget::new(render_config.screenshot_resolution);
+        self.render_tiles(render_config, true, &target, &mut |_| {}, &CancellationToken::new());
 
-                    let ray_vector = ray_vector.rotate_z(&V3::ZERO, self.rotation.1);
-                    let ray_vector = ray_vector.rotate_y(&V3::ZERO, self.rotation.0);
+        (target.get_pixel_data(), target.get_sample_counts())
+    }
 
-                    let ray = Line::new(&self.position, &ray_vector);
+    // Renders tiles in spiral order on every CPU, calling 'on_progress' on this thread as they finish
+    // Returns false if the render was cancelled, leaving 'target' partially filled
+    pub fn render_tiles(&self, render_config: &RenderConfig, is_screenshot: bool, target: &RenderTarget,
+        on_progress: &mut dyn FnMut(&RenderProgress), cancellation: &CancellationToken) -> bool {
+        const TILE_SIZE: u32 = 32;
 
-                    let colour = super::get_colour(ray, render_config, rng, is_screenshot).as_u8();
+        let resolution = target.resolution;
+        let tiles = Tile::get_spiral(resolution, TILE_SIZE);
+        let next_tile = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        let start = Instant::now();
 
-                    result.push((colour.0, colour.1, colour.2));
+        thread::scope(|scope| {
+            for _ in 0..num_cpus::get() {
+                let sender = sender.clone();
+                let (tiles, next_tile) = (&tiles, &next_tile);
+
+                scope.spawn(move || {
+                    let mut sampler = render_config.create_sampler(is_screenshot);
+
+                    while !cancellation.is_cancelled() {
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        if index >= tiles.len() { break; }
+                        let tile = &tiles[index];
+
+                        // Starts from whatever the target already holds, so resumed renders only add the missing samples
+                        let mut pixels = target.read_tile(tile);
+                        for (i, pixel) in pixels.iter_mut().enumerate() {
+                            let x = tile.x + (i as u32 % tile.width);
+                            let y = tile.y + (i as u32 / tile.width);
+                            self.accumulate_pixel(&ImagePixel { x, y, resolution, is_screenshot }, render_config, sampler.as_mut(), pixel);
+                        }
+
+                        target.write_tile(tile, &pixels);
+                        sender.send(()).unwrap();
+                    }
+                });
+            }
+            drop(sender);
+
+            // Report at least every 100ms so callers can stay responsive while tiles are slow
+            let mut completed_tiles = 0;
+            loop {
+                match receiver.recv_timeout(Duration::from_millis(100)) {
+                    Ok(()) => completed_tiles += 1,
+                    Err(RecvTimeoutError::Timeout) => {},
+                    Err(RecvTimeoutError::Disconnected) => break,
                 }
-
-                result
-            })
-        }
-        ).collect();
-
-        let mut flattened_data = Vec::with_capacity((resolution.0 * resolution.1 * 3) as usize);
-
-        for result in data {
-            for c in result {
-                flattened_data.push(c.0);
-                flattened_data.push(c.1);
-                flattened_data.push(c.2);
+                on_progress(&RenderProgress { completed_tiles, total_tiles: tiles.len(), elapsed: start.elapsed() });
             }
-        }
+        });
 
-        flattened_data
+        !cancellation.is_cancelled()
     }
 
     //noinspection DuplicatedCode
@@ -127,7 +357,7 @@ impl Camera {
         if is_screenshot { resolution = render_config.screenshot_resolution }
         else { resolution = render_config.resolution; }
 
-        let mut rng = thread_rng();
+        let mut sampler = render_config.create_sampler(is_screenshot);
 
         println!("Thread[{}] start", thread_id);
         let mut progress: usize = 0;
@@ -142,21 +372,8 @@ impl Camera {
             let x = (i + offset) % resolution.0 as usize;
             let y = (i + offset) / resolution.0 as usize;
 
-            let ray_vector = (V3::FORWARD * cam.fov) + 
-                V3::new(0.0, 
-                    -(((y as i32) - ((resolution.1)/2) as i32) as f64) / 
-                        ((resolution.1 as f64) / (2.0)), 
-                    (((x as i32) - ((resolution.0)/2) as i32) as f64) / 
-                        ((resolution.0 as f64) / ((resolution.0 as f64 / resolution.1 as f64) * 2.0))
-                );
-
-            let ray_vector = ray_vector.rotate_z(&V3::ZERO, cam.rotation.1);
-            let ray_vector = ray_vector.rotate_y(&V3::ZERO, cam.rotation.0);
-
-            let ray = Line::new(&cam.position,
-                &ray_vector);
-
-            let colour = super::get_colour(ray, render_config, &mut rng, is_screenshot).as_u8();
+            let (colour, _) = cam.get_pixel_colour(x as u32, y as u32, resolution, render_config, sampler.as_mut(), is_screenshot);
+            let colour = colour.ceiling_limited().as_u8();
     
             chunk[i*3] = colour.0;
             chunk[i*3 + 1] = colour.1;
@@ -168,12 +385,19 @@ impl Camera {
         // data
     }
 
-    pub fn get_image(&self, render_config: &RenderConfig, rng: &mut ThreadRng, is_screenshot: bool, verbose: bool) -> Vec<u8> {
+    pub fn get_image(&self, render_config: &RenderConfig, is_screenshot: bool, verbose: bool) -> Vec<u8> {
+        super::get_pixel_data(&self.get_linear_image(render_config, is_screenshot, verbose))
+    }
+
+    // Unclamped colour of every pixel, for the denoiser
+    pub fn get_linear_image(&self, render_config: &RenderConfig, is_screenshot: bool, verbose: bool) -> Vec<Colour> {
         let resolution;
         if is_screenshot { resolution = render_config.screenshot_resolution }
         else { resolution = render_config.resolution; }
 
-        let mut data = Vec::with_capacity((resolution.0 * resolution.1 * 3) as usize);
+        let mut sampler = render_config.create_sampler(is_screenshot);
+
+        let mut data = Vec::with_capacity((resolution.0 * resolution.1) as usize);
         
         let mut progress: u32 = 0;
         let increment: u32 = (resolution.0 * resolution.1) / 100;
@@ -187,27 +411,164 @@ impl Camera {
                     println!("{}%", progress);
                 }
 
-                let ray_vector = (V3::FORWARD * self.fov) + 
-                    V3::new(0.0, 
-                        -(((y as i32) - ((resolution.1)/2) as i32) as f64) / 
-                            ((resolution.1 as f64) / (2.0)), 
-                        (((x as i32) - ((resolution.0)/2) as i32) as f64) / 
-                            ((resolution.0 as f64) / ((resolution.0 as f64 / resolution.1 as f64) * 2.0))
-                    );
+                let (colour, _) = self.get_pixel_colour(x, y, resolution, render_config, sampler.as_mut(), is_screenshot);
+                data.push(colour);
+            }
+        }
+
+        data
+    }
+}
 
-                let ray_vector = ray_vector.rotate_z(&V3::ZERO, self.rotation.1);
-                let ray_vector = ray_vector.rotate_y(&V3::ZERO, self.rotation.0);
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
 
-                let ray = Line::new(&self.position, &ray_vector);
+    use crate::maths::{vectors::V3, lines::Line, quaternion::Quaternion, sampler::{Sampler, IndependentSampler}};
 
-                let colour = super::get_colour(ray, render_config, rng, is_screenshot).as_u8();
-        
-                data.push(colour.0);
-                data.push(colour.1);
-                data.push(colour.2);
+    use super::Camera;
+
+    fn get_camera() -> Camera {
+        Camera::look_at(&V3::new(1.0, 2.0, 3.0), &V3::new(4.0, 1.0, -2.0), &V3::UP, 60.0)
+    }
+
+    // Where 'ray' crosses the plane 'focus_distance' in front of the camera
+    fn get_focal_plane_point(camera: &Camera, ray: &Line) -> V3 {
+        let forward = camera.orientation.get_forward();
+        let scale = (camera.focus_distance - (ray.point - camera.position).dot(&forward)) / ray.vector.dot(&forward);
+        ray.point + (ray.vector * scale)
+    }
+
+    #[test]
+    fn rays_through_a_pixel_meet_on_the_focal_plane() {
+        let camera = get_camera().with_aperture(0.5, 7.0);
+        let mut rng = IndependentSampler::new(0);
+        let focus = get_focal_plane_point(&camera, &camera.get_pinhole_ray((30.0, 20.0), (64, 48)).unwrap());
+
+        for i in 0..100 {
+            rng.start_sample((0, 0), i);
+            let ray = camera.get_ray((30.0, 20.0), (64, 48), &mut rng).unwrap();
+
+            assert!((ray.point - camera.position).magnitude() <= 0.5 + 1e-9);
+            assert!((get_focal_plane_point(&camera, &ray) - focus).magnitude() < 1e-9, "{:?} vs {:?}", get_focal_plane_point(&camera, &ray), focus);
+        }
+    }
+
+    // Average squared distance from the centre of the lens, and the furthest any sample got
+    fn get_lens_spread(camera: &Camera) -> (f64, f64) {
+        let mut rng = IndependentSampler::new(1);
+        let count = 20000;
+        let (mut total, mut furthest) = (0.0, 0.0f64);
+
+        for i in 0..count {
+            rng.start_sample((0, 0), i);
+            let point = camera.get_lens_point(&mut rng);
+            assert_eq!(point.x, 0.0);
+            total += point.dot(&point);
+            furthest = furthest.max(point.magnitude());
+        }
+
+        (total / count as f64, furthest)
+    }
+
+    #[test]
+    fn circular_aperture_samples_are_uniform_over_the_disc() {
+        let (mean_squared, furthest) = get_lens_spread(&get_camera().with_aperture(0.5, 7.0));
+
+        assert!(furthest <= 0.5);
+        assert!((mean_squared - 0.5 * 0.25).abs() < 0.01 * 0.25, "{}", mean_squared);
+    }
+
+    #[test]
+    fn bladed_aperture_samples_are_uniform_over_the_polygon() {
+        let camera = get_camera().with_aperture(0.5, 7.0).with_blades(6, 0.3);
+        let segment = 2.0 * PI / 6.0;
+        let mut rng = IndependentSampler::new(2);
+
+        // Inside every edge, whose normals point between the corners
+        for i in 0..2000 {
+            rng.start_sample((0, 0), i);
+            let point = camera.get_lens_point(&mut rng);
+            for edge in 0..6 {
+                let angle = 0.3 + segment * (edge as f64 + 0.5);
+                assert!(point.y * angle.sin() + point.z * angle.cos() <= 0.5 * (segment / 2.0).cos() + 1e-9);
             }
         }
 
-        data
+        // Each triangle from the centre to two corners has a mean squared distance of (|a|^2 + |b|^2 + a.b) / 6
+        let (mean_squared, _) = get_lens_spread(&camera);
+        let expected = 0.25 * (2.0 + segment.cos()) / 6.0;
+        assert!((mean_squared - expected).abs() < 0.01 * expected, "{} vs {}", mean_squared, expected);
+    }
+
+    #[test]
+    fn look_at_points_the_centre_ray_at_the_target() {
+        let camera = get_camera();
+        let ray = camera.get_centre_ray();
+
+        assert!((ray.vector.normalised() - (V3::new(4.0, 1.0, -2.0) - camera.position).normalised()).magnitude() < 1e-9);
+        assert!(camera.orientation.get_right().y.abs() < 1e-9);
+    }
+
+    #[test]
+    fn roll_turns_about_the_view_without_moving_it() {
+        let mut camera = Camera::new(&V3::ZERO, Quaternion::from_axis_angle(&V3::UP, 0.8), 60.0);
+        let forward = camera.orientation.get_forward();
+        camera.rotate_roll(0.6);
+
+        assert!((camera.orientation.get_forward() - forward).magnitude() < 1e-9);
+        assert!((camera.orientation.get_right().y.abs() - 0.6f64.sin()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn yaw_keeps_the_horizon_level_and_is_reported_back() {
+        let mut camera = Camera::new(&V3::ZERO, Quaternion::IDENTITY, 60.0);
+        camera.rotate_pitch(0.4);
+        camera.rotate_yaw(1.1);
+
+        assert!(camera.orientation.get_right().y.abs() < 1e-9);
+        assert!((camera.get_yaw() - 1.1).abs() < 1e-9, "{}", camera.get_yaw());
+    }
+
+    #[test]
+    fn pitch_stops_before_passing_straight_up() {
+        let mut camera = Camera::new(&V3::ZERO, Quaternion::IDENTITY, 60.0);
+        for _ in 0..40 { camera.rotate_pitch(0.1); }
+
+        let forward = camera.orientation.get_forward();
+        assert!(forward.y > 0.99 && forward.x > 0.0, "{:?}", forward);
+
+        for _ in 0..80 { camera.rotate_pitch(-0.1); }
+
+        let forward = camera.orientation.get_forward();
+        assert!(forward.y < -0.99 && forward.x > 0.0, "{:?}", forward);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rays_are_spread_over_the_shutter() {
+        let mut rng = IndependentSampler::new(0);
+        let mut get_times = |camera: &Camera| (0..1000).map(|i| {
+            rng.start_sample((0, 0), i);
+            camera.get_ray((1.0, 1.0), (2, 2), &mut rng).unwrap().time
+        }).collect::<Vec<f64>>();
+
+        assert!(get_times(&get_camera()).iter().all(|t| *t == 0.0));
+
+        let times = get_times(&get_camera().with_shutter(0.25, 0.75));
+        let mean = times.iter().sum::<f64>() / times.len() as f64;
+        assert!(times.iter().all(|t| *t >= 0.25 && *t <= 0.75));
+        assert!((mean - 0.5).abs() < 0.02, "{}", mean);
+    }
+
+    #[test]
+    fn moving_camera_is_where_it_is_at_each_ray_time() {
+        let camera = get_camera().with_shutter(0.0, 1.0).with_motion(&V3::new(11.0, 2.0, 3.0), get_camera().orientation);
+        let mut rng = IndependentSampler::new(0);
+
+        for i in 0..100 {
+            rng.start_sample((0, 0), i);
+            let ray = camera.get_ray((1.0, 1.0), (2, 2), &mut rng).unwrap();
+            assert!((ray.point - V3::new(1.0 + 10.0 * ray.time, 2.0, 3.0)).magnitude() < 1e-9, "{:?} at {}", ray.point, ray.time);
+        }
+    }
+}
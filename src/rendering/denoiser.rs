@@ -0,0 +1,91 @@
+use rayon::prelude::*;
+
+use crate::{colour::Colour, maths::vectors::V3};
+
+// Surface properties at the first hit of each pixel, used to tell noise apart from real edges
+pub struct FeatureBuffers {
+    pub albedo: Vec<Colour>,
+    pub normal: Vec<V3>, // Zero where nothing was hit
+    pub depth: Vec<f64>, // f64::MAX where nothing was hit
+}
+
+// Edge-avoiding a-trous wavelet filter (Dammertz et al. 2010)
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct Denoiser {
+    pub iterations: u32, // Each iteration doubles the filter's reach
+    pub colour_sigma: f64,
+    pub normal_sigma: f64,
+    pub depth_sigma: f64,
+    pub albedo_sigma: f64,
+}
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+#[allow(dead_code)]
+impl Denoiser {
+    pub fn new(iterations: u32) -> Self {
+        Self { iterations, colour_sigma: 0.5, normal_sigma: 0.1, depth_sigma: 0.5, albedo_sigma: 0.1 }
+    }
+
+    // Works on linear colour, before it is clamped into 8 bits, so bright noise isn't clipped before it can be spread out
+    pub fn denoise(&self, colours: &[Colour], features: &FeatureBuffers, resolution: (u32, u32)) -> Vec<Colour> {
+        let (width, height) = (resolution.0 as i32, resolution.1 as i32);
+
+        // Filter lighting rather than final colour so texture detail isn't blurred away
+        let mut illumination: Vec<Colour> = colours.iter().zip(features.albedo.iter()).map(|(colour, albedo)| Self::demodulate(colour, albedo)).collect();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let colour_sigma = self.colour_sigma / (1 << iteration) as f64;
+            let input = &illumination;
+
+            illumination = (0..(width * height)).into_par_iter().map(|i| {
+                let (x, y) = (i % width, i / width);
+                let p = i as usize;
+
+                let mut total_colour = Colour::BLACK;
+                let mut total_weight = 0.0;
+                for (ky, hy) in KERNEL.iter().enumerate() {
+                    for (kx, hx) in KERNEL.iter().enumerate() {
+                        let qx = x + (kx as i32 - 2) * step;
+                        let qy = y + (ky as i32 - 2) * step;
+                        if qx < 0 || qy < 0 || qx >= width || qy >= height { continue; }
+                        let q = (qy * width + qx) as usize;
+
+                        let weight = hx * hy *
+                            Self::get_weight(input[p].distance(&input[q]), colour_sigma) *
+                            Self::get_weight(features.albedo[p].distance(&features.albedo[q]), self.albedo_sigma) *
+                            Self::get_weight((features.normal[p] - features.normal[q]).magnitude(), self.normal_sigma) *
+                            Self::get_weight((features.depth[p] - features.depth[q]).abs() / features.depth[p].max(1.0), self.depth_sigma);
+
+                        total_colour = total_colour + (input[q] * weight);
+                        total_weight += weight;
+                    }
+                }
+
+                // The centre pixel always has a weight of at least 9/64, so this never divides by zero
+                total_colour / total_weight
+            }).collect();
+        }
+
+        illumination.iter().zip(features.albedo.iter()).map(|(light, albedo)| Self::remodulate(light, albedo)).collect()
+    }
+
+    fn get_weight(distance: f64, sigma: f64) -> f64 {
+        if distance.is_nan() { return 0.0; }
+        (-(distance * distance) / (sigma * sigma)).exp()
+    }
+
+    fn demodulate(colour: &Colour, albedo: &Colour) -> Colour {
+        let (r, g, b) = colour.as_f64();
+        let (ar, ag, ab) = albedo.as_f64();
+        Colour::from_f64(r / ar.max(0.01), g / ag.max(0.01), b / ab.max(0.01))
+    }
+
+    fn remodulate(illumination: &Colour, albedo: &Colour) -> Colour {
+        let (r, g, b) = illumination.as_f64();
+        let (ar, ag, ab) = albedo.as_f64();
+        Colour::from_f64(r * ar.max(0.01), g * ag.max(0.01), b * ab.max(0.01))
+    }
+}
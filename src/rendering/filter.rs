@@ -0,0 +1,91 @@
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box(f64), // Radius in pixels
+    Tent(f64), // Radius in pixels
+    Gaussian(f64, f64), // Radius in pixels, falloff
+    Mitchell(f64, f64, f64), // Radius in pixels, B, C - 1/3, 1/3 is the recommended balance
+}
+
+#[allow(dead_code)]
+impl Filter {
+    pub fn get_radius(&self) -> f64 {
+        match self {
+            Filter::Box(r) | Filter::Tent(r) | Filter::Gaussian(r, _) | Filter::Mitchell(r, _, _) => *r,
+        }
+    }
+
+    // Weight of a sample at an offset in pixels from the pixel centre
+    pub fn get_weight(&self, offset: (f64, f64)) -> f64 {
+        self.get_weight_1d(offset.0) * self.get_weight_1d(offset.1)
+    }
+
+    fn get_weight_1d(&self, x: f64) -> f64 {
+        let radius = self.get_radius();
+        if x.abs() > radius { return 0.0; }
+
+        match self {
+            Filter::Box(_) => 1.0,
+            Filter::Tent(_) => radius - x.abs(),
+            Filter::Gaussian(_, alpha) => ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0),
+            Filter::Mitchell(_, b, c) => {
+                // Defined over [-2, 2], stretched to the radius
+                let x = (2.0 * x / radius).abs();
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)) / 6.0
+                }
+                else {
+                    ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)) / 6.0
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+
+    const FILTERS: [Filter; 4] = [Filter::Box(0.5), Filter::Tent(1.5), Filter::Gaussian(2.0, 2.0), Filter::Mitchell(2.0, 1.0 / 3.0, 1.0 / 3.0)];
+
+    #[test]
+    fn weights_peak_at_the_centre_and_vanish_beyond_the_radius() {
+        for filter in FILTERS {
+            let radius = filter.get_radius();
+            let centre = filter.get_weight((0.0, 0.0));
+
+            assert!(centre > 0.0, "{:?}", filter);
+            assert_eq!(filter.get_weight((radius * 1.01, 0.0)), 0.0, "{:?}", filter);
+            assert_eq!(filter.get_weight((0.0, -radius * 1.01)), 0.0, "{:?}", filter);
+
+            for i in 1..20 {
+                let x = radius * i as f64 / 20.0;
+                assert!(filter.get_weight((x, 0.0)) <= centre, "{:?} at {}", filter, x);
+            }
+        }
+    }
+
+    #[test]
+    fn weights_are_symmetric_and_separable() {
+        for filter in FILTERS {
+            for (x, y) in [(0.1, 0.2), (0.3, -0.4), (-0.45, 0.05)] {
+                let weight = filter.get_weight((x, y));
+                assert!((weight - filter.get_weight((-x, y))).abs() < 1e-12 && (weight - filter.get_weight((y, x))).abs() < 1e-12, "{:?}", filter);
+                assert!((weight * filter.get_weight((0.0, 0.0)) - filter.get_weight((x, 0.0)) * filter.get_weight((0.0, y))).abs() < 1e-12, "{:?}", filter);
+            }
+        }
+    }
+
+    // Weights of pixel-spaced samples add up to the same total wherever they fall, so flat images stay flat
+    #[test]
+    fn tent_and_mitchell_weights_sum_to_one_across_pixels() {
+        let filters = [Filter::Tent(1.0), Filter::Mitchell(2.0, 1.0 / 3.0, 1.0 / 3.0), Filter::Mitchell(2.0, 1.0, 0.0), Filter::Mitchell(2.0, 0.0, 0.5)];
+
+        for filter in filters {
+            for offset in [0.0, 0.1, 0.25, 0.5, 0.9] {
+                let total: f64 = (-3..=3).map(|n| filter.get_weight_1d(offset + n as f64)).sum();
+                assert!((total - 1.0).abs() < 1e-9, "{:?} at {}: {}", filter, offset, total);
+            }
+        }
+    }
+}
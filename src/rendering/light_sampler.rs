@@ -0,0 +1,219 @@
+use crate::{maths::{vectors::V3, distribution::Distribution1D}, lights::{Light, ObjectLight, EnvironmentLight}};
+
+use super::RenderConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSource {
+    Light(usize), // Index into scene_lights
+    Object(usize), // Index into scene_objects
+    Environment, // The render config's environment
+}
+
+#[derive(Debug, Clone)]
+struct LightNode {
+    bounds: (V3, V3),
+    power: f64,
+    children: Option<(usize, usize)>,
+    source: Option<LightSource>,
+}
+
+// Chooses lights in proportion to how much they are likely to contribute so that direct lighting
+// cost doesn't grow with the number of lights
+#[allow(dead_code)]
+pub struct LightSampler {
+    sources: Vec<LightSource>,
+    distribution: Distribution1D,
+    infinite_sources: Vec<LightSource>,
+    nodes: Vec<LightNode>,
+}
+
+#[allow(dead_code)]
+impl LightSampler {
+    pub fn new(render_config: &RenderConfig) -> Self {
+        let mut sources = Vec::new();
+        let mut powers = Vec::new();
+        let mut infinite_sources = Vec::new();
+        let mut bounded = Vec::new();
+
+        let mut add = |source: LightSource, light: &dyn Light| {
+            let power = light.get_power();
+            sources.push(source);
+            powers.push(power);
+            match light.get_bounds() {
+                None => infinite_sources.push(source),
+                Some(bounds) => bounded.push(LightNode { bounds, power, children: None, source: Some(source) }),
+            }
+        };
+
+        for i in 0..render_config.scene_lights.len() {
+            add(LightSource::Light(i), render_config.scene_lights[i].as_ref());
+        }
+        for i in 0..render_config.scene_objects.len() {
+            if !render_config.scene_objects[i].get_surface_type().is_emissive() { continue; }
+            add(LightSource::Object(i), &ObjectLight::new(render_config.scene_objects[i].as_ref()));
+        }
+        if let Some(environment) = &render_config.environment {
+            add(LightSource::Environment, &EnvironmentLight::new(environment.clone(), 1.0));
+        }
+
+        let mut nodes = Vec::with_capacity(bounded.len() * 2);
+        if !bounded.is_empty() {
+            Self::build(&mut nodes, bounded);
+        }
+
+        Self { distribution: Distribution1D::new(&powers), sources, infinite_sources, nodes }
+    }
+
+    // Splits along the longest axis at the median, returning the index of the created node
+    fn build(nodes: &mut Vec<LightNode>, mut leaves: Vec<LightNode>) -> usize {
+        if leaves.len() == 1 {
+            nodes.push(leaves.pop().unwrap());
+            return nodes.len() - 1;
+        }
+
+        let bounds = leaves.iter().fold(leaves[0].bounds, |b, l| (b.0.min(&l.bounds.0), b.1.max(&l.bounds.1)));
+        let power = leaves.iter().map(|l| l.power).sum();
+
+        let extent = bounds.1 - bounds.0;
+        let axis = |v: &V3| if extent.x >= extent.y && extent.x >= extent.z { v.x } else if extent.y >= extent.z { v.y } else { v.z };
+        leaves.sort_by(|a, b| axis(&(a.bounds.0 + a.bounds.1)).total_cmp(&axis(&(b.bounds.0 + b.bounds.1))));
+
+        let right_leaves = leaves.split_off(leaves.len() / 2);
+
+        let index = nodes.len();
+        nodes.push(LightNode { bounds, power, children: None, source: None });
+        let left = Self::build(nodes, leaves);
+        let right = Self::build(nodes, right_leaves);
+        nodes[index].children = Some((left, right));
+
+        index
+    }
+
+    pub fn get_sources(&self) -> &[LightSource] {
+        &self.sources
+    }
+
+    pub fn get_infinite_sources(&self) -> &[LightSource] {
+        &self.infinite_sources
+    }
+
+    // Returns (source, probability of choosing it) chosen by power alone
+    pub fn sample_power(&self, u: f64) -> Option<(LightSource, f64)> {
+        if self.sources.is_empty() { return None; }
+
+        let (index, probability, _) = self.distribution.sample(u);
+        Some((self.sources[index], probability))
+    }
+
+    // Returns (source, probability of choosing it) from the lights with bounds, favouring those close to 'position'
+    pub fn sample_tree(&self, position: &V3, u: f64) -> Option<(LightSource, f64)> {
+        if self.nodes.is_empty() { return None; }
+
+        let mut u = u;
+        let mut probability = 1.0;
+        let mut node = &self.nodes[0];
+
+        while let Some((left, right)) = node.children {
+            let left_importance = Self::get_importance(&self.nodes[left], position);
+            let right_importance = Self::get_importance(&self.nodes[right], position);
+            if left_importance + right_importance <= 0.0 { return None; }
+
+            let left_probability = left_importance / (left_importance + right_importance);
+            if u < left_probability {
+                u /= left_probability;
+                probability *= left_probability;
+                node = &self.nodes[left];
+            }
+            else {
+                u = (u - left_probability) / (1.0 - left_probability);
+                probability *= 1.0 - left_probability;
+                node = &self.nodes[right];
+            }
+        }
+
+        Some((node.source.unwrap(), probability))
+    }
+
+    fn get_importance(node: &LightNode, position: &V3) -> f64 {
+        let centre = (node.bounds.0 + node.bounds.1) * 0.5;
+        let radius_squared = ((node.bounds.1 - node.bounds.0) * 0.5).dot(&((node.bounds.1 - node.bounds.0) * 0.5));
+        let distance_squared = (centre - position).dot(&(centre - position));
+
+        // Don't let the importance blow up when inside the node's bounds
+        node.power / distance_squared.max(radius_squared).max(1e-6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colour::Colour;
+    use crate::lights::{Light, PointLight, DirectionalLight};
+    use crate::maths::vectors::V3;
+    use crate::rendering::RenderConfig;
+
+    use super::LightSource;
+
+    // Point lights of different powers spread along x, and a directional light
+    fn get_render_config() -> RenderConfig {
+        let mut lights: Vec<Box<dyn Light + Sync + Send>> = [(0.0, 1.0), (2.0, 3.0), (5.0, 2.0), (9.0, 6.0), (10.0, 0.5)].iter()
+            .map(|(x, intensity)| Box::new(PointLight::new(&V3::new(*x, 1.0, 0.0), &Colour::WHITE, *intensity)) as Box<dyn Light + Sync + Send>)
+            .collect();
+        lights.push(Box::new(DirectionalLight::new(&V3::DOWN, &Colour::WHITE, 2.0)));
+        RenderConfig::for_tests(Vec::new(), lights)
+    }
+
+    // Every source the sampler can choose with the probability it reports, found by sweeping 'u'
+    fn get_probabilities(sample: impl Fn(f64) -> Option<(LightSource, f64)>) -> Vec<(LightSource, f64, f64)> {
+        let count = 100000;
+        let mut found: Vec<(LightSource, f64, f64)> = Vec::new();
+
+        for i in 0..count {
+            let (source, probability) = sample((i as f64 + 0.5) / count as f64).unwrap();
+            match found.iter_mut().find(|(s, _, _)| *s == source) {
+                Some(entry) => entry.2 += 1.0 / count as f64,
+                None => found.push((source, probability, 1.0 / count as f64)),
+            }
+        }
+
+        found
+    }
+
+    #[test]
+    fn power_selection_probabilities_are_proportional_to_power() {
+        let render_config = get_render_config();
+        let sampler = render_config.get_light_sampler();
+        let found = get_probabilities(|u| sampler.sample_power(u));
+        let total_power: f64 = render_config.scene_lights.iter().map(|light| light.get_power()).sum();
+
+        assert_eq!(found.len(), render_config.scene_lights.len());
+        for (source, probability, frequency) in found {
+            let LightSource::Light(i) = source else { panic!("{:?}", source) };
+            assert!((probability - render_config.scene_lights[i].get_power() / total_power).abs() < 1e-9);
+            assert!((frequency - probability).abs() < 1e-3, "{:?}: {} chosen {}", source, probability, frequency);
+        }
+    }
+
+    #[test]
+    fn tree_selection_probabilities_sum_to_one_and_favour_nearby_lights() {
+        let render_config = get_render_config();
+        let sampler = render_config.get_light_sampler();
+
+        for position in [V3::ZERO, V3::new(9.5, 0.0, 0.0), V3::new(4.0, 20.0, 3.0)] {
+            let found = get_probabilities(|u| sampler.sample_tree(&position, u));
+
+            // The directional light has no bounds so is always sampled, rather than chosen from the tree
+            assert_eq!(found.len(), render_config.scene_lights.len() - 1);
+            assert!(!found.iter().any(|(source, _, _)| *source == LightSource::Light(5)));
+            assert!((found.iter().map(|(_, probability, _)| probability).sum::<f64>() - 1.0).abs() < 1e-9);
+            for (source, probability, frequency) in &found {
+                assert!((frequency - probability).abs() < 1e-3, "{:?}: {} chosen {}", source, probability, frequency);
+            }
+        }
+
+        // Next to the weak light at x = 10 it is chosen more often than the brighter one at x = 0
+        let found = get_probabilities(|u| sampler.sample_tree(&V3::new(10.0, 0.0, 0.0), u));
+        let probability_of = |i: usize| found.iter().find(|(source, _, _)| *source == LightSource::Light(i)).unwrap().1;
+        assert!(probability_of(4) > probability_of(0));
+        assert_eq!(sampler.get_infinite_sources(), &[LightSource::Light(5)]);
+    }
+}
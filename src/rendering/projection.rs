@@ -0,0 +1,151 @@
+use std::f64::consts::PI;
+
+use crate::maths::vectors::V3;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum FisheyeMapping {
+    Equidistant, // Distance from the centre is proportional to the angle
+    Equisolid, // Area on the image is proportional to solid angle
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum Projection {
+    Perspective,
+    Orthographic(f64), // Height of the view in world units
+    Fisheye(FisheyeMapping, f64), // Field of view across the image height in radians
+    Equirectangular, // Full 360 x 180 degree view
+}
+
+#[allow(dead_code)]
+impl Projection {
+    // Camera space ray origin and vector through a position on the image, None if it falls outside the projection
+    // 'fov' is the perspective vertical field of view in radians
+    pub fn get_ray(&self, position: (f64, f64), resolution: (u32, u32), fov: f64) -> Option<(V3, V3)> {
+        // Screen coordinates, scaled so the image height spans -1 to 1
+        let half_height = resolution.1 as f64 / 2.0;
+        let sx = (position.0 - (resolution.0 / 2) as f64) / half_height;
+        let sy = -(position.1 - (resolution.1 / 2) as f64) / half_height;
+
+        match self {
+            Projection::Perspective => Some((V3::ZERO, (V3::FORWARD / (fov / 2.0).tan()) + V3::new(0.0, sy, sx))),
+            Projection::Orthographic(height) => Some((V3::new(0.0, sy, sx) * (height / 2.0), V3::FORWARD)),
+            Projection::Fisheye(mapping, fisheye_fov) => {
+                let r = (sx * sx + sy * sy).sqrt();
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * (fisheye_fov / 2.0),
+                    FisheyeMapping::Equisolid => {
+                        let s = r * (fisheye_fov / 4.0).sin();
+                        if s > 1.0 { return None; }
+                        2.0 * s.asin()
+                    }
+                };
+                if theta > fisheye_fov.min(2.0 * PI) / 2.0 + 1e-9 { return None; }
+                if r == 0.0 { return Some((V3::ZERO, V3::FORWARD)); }
+
+                Some((V3::ZERO, V3::new(theta.cos(), theta.sin() * sy / r, theta.sin() * sx / r)))
+            }
+            Projection::Equirectangular => {
+                let longitude = (position.0 / resolution.0 as f64 - 0.5) * 2.0 * PI;
+                let latitude = (0.5 - position.1 / resolution.1 as f64) * PI;
+
+                Some((V3::ZERO, V3::new(
+                    latitude.cos() * longitude.cos(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.sin()
+                )))
+            }
+        }
+    }
+
+    // Whether rays share a flat focal plane, otherwise the focus distance is measured along each ray
+    pub fn is_planar(&self) -> bool {
+        matches!(self, Projection::Perspective | Projection::Orthographic(_))
+    }
+
+    pub fn next(&self) -> Projection {
+        match self {
+            Projection::Perspective => Projection::Orthographic(10.0),
+            Projection::Orthographic(_) => Projection::Fisheye(FisheyeMapping::Equidistant, PI),
+            Projection::Fisheye(FisheyeMapping::Equidistant, _) => Projection::Fisheye(FisheyeMapping::Equisolid, PI),
+            Projection::Fisheye(FisheyeMapping::Equisolid, _) => Projection::Equirectangular,
+            Projection::Equirectangular => Projection::Perspective,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::maths::vectors::V3;
+
+    use super::{Projection, FisheyeMapping};
+
+    const RESOLUTION: (u32, u32) = (200, 100);
+
+    fn get_vector(projection: &Projection, position: (f64, f64)) -> V3 {
+        projection.get_ray(position, RESOLUTION, PI / 2.0).unwrap().1.normalised()
+    }
+
+    fn assert_close(a: &V3, b: &V3) {
+        assert!((a - b).magnitude() < 1e-9, "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn perspective_spans_the_field_of_view() {
+        let projection = Projection::Perspective;
+
+        assert_close(&get_vector(&projection, (100.0, 50.0)), &V3::FORWARD);
+        assert!((get_vector(&projection, (100.0, 0.0)).angle_to(&V3::FORWARD) - PI / 4.0).abs() < 1e-9);
+        assert!(get_vector(&projection, (100.0, 0.0)).y > 0.0 && get_vector(&projection, (200.0, 50.0)).z > 0.0);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_and_span_the_height() {
+        let projection = Projection::Orthographic(4.0);
+        let (top, top_vector) = projection.get_ray((100.0, 0.0), RESOLUTION, PI / 2.0).unwrap();
+        let (bottom, bottom_vector) = projection.get_ray((100.0, 100.0), RESOLUTION, PI / 2.0).unwrap();
+
+        assert_close(&top_vector, &V3::FORWARD);
+        assert_close(&bottom_vector, &V3::FORWARD);
+        assert_close(&(top - bottom), &(V3::UP * 4.0));
+    }
+
+    #[test]
+    fn equidistant_fisheye_angle_grows_with_distance_from_the_centre() {
+        let projection = Projection::Fisheye(FisheyeMapping::Equidistant, PI);
+
+        for (offset, angle) in [(0.0, 0.0), (12.5, PI / 8.0), (25.0, PI / 4.0), (50.0, PI / 2.0)] {
+            assert!((get_vector(&projection, (100.0 + offset, 50.0)).angle_to(&V3::FORWARD) - angle).abs() < 1e-9, "{}", offset);
+        }
+        assert!(projection.get_ray((100.0, 50.0 - 60.0), RESOLUTION, PI / 2.0).is_none());
+    }
+
+    #[test]
+    fn equisolid_fisheye_area_is_proportional_to_solid_angle() {
+        let projection = Projection::Fisheye(FisheyeMapping::Equisolid, PI);
+
+        // The solid angle of the cone out to 'offset' is 2 pi (1 - cos theta), which should grow with the square of 'offset'
+        let ratios: Vec<f64> = [5.0, 20.0, 35.0, 50.0].iter().map(|offset| {
+            (1.0 - get_vector(&projection, (100.0, 50.0 + offset)).x) / (offset * offset)
+        }).collect();
+
+        for ratio in &ratios {
+            assert!((ratio - ratios[0]).abs() < 1e-9 * ratios[0], "{:?}", ratios);
+        }
+    }
+
+    #[test]
+    fn equirectangular_covers_every_direction() {
+        let projection = Projection::Equirectangular;
+
+        assert_close(&get_vector(&projection, (100.0, 50.0)), &V3::FORWARD);
+        assert_close(&get_vector(&projection, (150.0, 50.0)), &V3::RIGHT);
+        assert_close(&get_vector(&projection, (50.0, 50.0)), &V3::LEFT);
+        assert_close(&get_vector(&projection, (0.0, 50.0)), &V3::BACK);
+        assert_close(&get_vector(&projection, (100.0, 0.0)), &V3::UP);
+        assert_close(&get_vector(&projection, (100.0, 100.0)), &V3::DOWN);
+    }
+}
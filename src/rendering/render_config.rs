@@ -1,5 +1,26 @@
-use crate::{objects::Object, colour::{colour_getters::ColourGetter, Colour}, lights::Light};
+use std::sync::{Arc, OnceLock};
 
+use crate::{objects::Object, colour::{colour_getters::ColourGetter, Colour}, lights::Light, environment::Environment, maths::sampler::{Sampler, SamplerType}};
+
+use crate::scene::SceneNode;
+
+use super::{LightSampler, Filter, Denoiser};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSampling {
+    Light,
+    Bsdf,
+    MultipleImportance,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSelection {
+    All, // Sample every light at every hit
+    Power, // Pick lights in proportion to their power
+    Tree, // Pick bounded lights from a tree favouring nearby ones, always sample lights at infinity
+}
 
 pub struct RenderConfig {
     pub resolution: (u32, u32),
@@ -9,14 +30,119 @@ pub struct RenderConfig {
     // pub diffusive_constant: f64,
     pub sky_height: f64,
     pub sky_scale: f64,
-    pub sky_texture: Box<dyn ColourGetter + Sync>,
+    pub sky_texture: Box<dyn ColourGetter + Sync + Send>,
+    pub environment: Option<Arc<dyn Environment + Sync + Send>>, // Replaces the sky plane when set, and lights the scene
     pub global_light: Colour,
-    pub scene_objects: Vec<Box<dyn Object + Sync>>,
-    pub scene_lights: Vec<Box<dyn Light + Sync>>,
+    pub scene_objects: Vec<Box<dyn Object + Sync + Send>>,
+    pub scene_lights: Vec<Box<dyn Light + Sync + Send>>,
     pub enable_full_bright: bool,
     pub screenshot_enable_full_bright: bool,
     pub enable_direct_lighting: bool,
     pub screenshot_enable_direct_lighting: bool,
     pub indirect_lighting_ray_count: u32,
     pub screenshot_indirect_lighting_ray_count: u32,
-}
\ No newline at end of file
+    pub shadow_samples: u32,
+    pub screenshot_shadow_samples: u32,
+    pub light_sampling: LightSampling,
+    pub light_selection: LightSelection,
+    pub light_sampler: OnceLock<LightSampler>, // Built on first use from the scene
+    pub pixel_samples: u32,
+    pub screenshot_pixel_samples: u32,
+    pub filter: Filter,
+    pub sampler: SamplerType,
+    pub seed: u64, // Renders with the same seed are identical however the work is split between threads
+    pub adaptive_threshold: Option<f64>, // Screenshot pixels stop sampling once the standard error of their luminance falls below this
+    pub adaptive_min_samples: u32,
+    pub save_sample_heatmap: bool,
+    pub denoise: bool,
+    pub screenshot_denoise: bool,
+    pub denoiser: Denoiser,
+    pub checkpoint_interval: Option<f64>, // Seconds between screenshot checkpoints
+}
+
+#[allow(dead_code)]
+impl RenderConfig {
+    pub fn get_light_sampler(&self) -> &LightSampler {
+        self.light_sampler.get_or_init(|| LightSampler::new(self))
+    }
+
+    pub fn get_pixel_samples(&self, is_screenshot: bool) -> u32 {
+        if is_screenshot { self.screenshot_pixel_samples.max(1) }
+        else { self.pixel_samples.max(1) }
+    }
+
+    pub fn get_direct_lighting(&self, is_screenshot: bool) -> bool {
+        if is_screenshot { self.screenshot_enable_direct_lighting }
+        else { self.enable_direct_lighting }
+    }
+
+    pub fn get_indirect_ray_count(&self, is_screenshot: bool) -> u32 {
+        if is_screenshot { self.screenshot_indirect_lighting_ray_count }
+        else { self.indirect_lighting_ray_count }
+    }
+
+    pub fn create_sampler(&self, is_screenshot: bool) -> Box<dyn Sampler> {
+        self.sampler.create(self.get_pixel_samples(is_screenshot), self.seed)
+    }
+
+    // Must be called after changing scene_lights or emissive scene_objects
+    pub fn invalidate_light_sampler(&mut self) {
+        self.light_sampler = OnceLock::new();
+    }
+
+    // Replaces the scene's objects and lights with those in 'scene'
+    pub fn set_scene(&mut self, scene: &SceneNode) {
+        (self.scene_objects, self.scene_lights) = scene.flatten();
+        self.invalidate_light_sampler();
+    }
+
+    // Moves every animated object and light to 'time' seconds
+    pub fn set_time(&mut self, time: f64) {
+        for object in self.scene_objects.iter() { object.set_time(time); }
+        for light in self.scene_lights.iter() { light.set_time(time); }
+        self.invalidate_light_sampler();
+    }
+}
+
+// A 1x1 render with one sample of everything and only direct lighting, for tests to adjust
+#[cfg(test)]
+impl RenderConfig {
+    pub fn for_tests(scene_objects: Vec<Box<dyn Object + Sync + Send>>, scene_lights: Vec<Box<dyn Light + Sync + Send>>) -> Self {
+        Self {
+            resolution: (1, 1),
+            screenshot_resolution: (1, 1),
+            max_reflections: 0,
+            screenshot_max_reflection: 0,
+            sky_height: 1000.0,
+            sky_scale: 5000.0,
+            sky_texture: Box::new(crate::colour::colour_getters::SolidColour { colour: Colour::BLACK }),
+            environment: None,
+            global_light: Colour::BLACK,
+            scene_objects,
+            scene_lights,
+            enable_full_bright: false,
+            screenshot_enable_full_bright: false,
+            enable_direct_lighting: true,
+            screenshot_enable_direct_lighting: true,
+            indirect_lighting_ray_count: 0,
+            screenshot_indirect_lighting_ray_count: 0,
+            shadow_samples: 1,
+            screenshot_shadow_samples: 1,
+            light_sampling: LightSampling::MultipleImportance,
+            light_selection: LightSelection::All,
+            light_sampler: OnceLock::new(),
+            pixel_samples: 1,
+            screenshot_pixel_samples: 1,
+            filter: Filter::Box(0.5),
+            sampler: SamplerType::Independent,
+            seed: 0,
+            adaptive_threshold: None,
+            adaptive_min_samples: 1,
+            save_sample_heatmap: false,
+            denoise: false,
+            screenshot_denoise: false,
+            denoiser: Denoiser::new(0),
+            checkpoint_interval: None,
+        }
+    }
+}
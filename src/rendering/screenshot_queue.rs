@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::{self, JoinHandle};
+
+use super::{Camera, RenderConfig, RenderTarget, RenderProgress, CancellationToken};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum JobStatus {
+    Queued,
+    Rendering(RenderProgress),
+    Finished,
+    Cancelled,
+}
+
+pub struct ScreenshotJob {
+    pub id: u32,
+    pub camera: Camera,
+    pub render_config: Arc<RenderConfig>, // The scene as it was when queued
+    pub target: RenderTarget, // Filled in as tiles finish, usable as a preview
+    pub cancellation: CancellationToken,
+    status: Mutex<JobStatus>,
+}
+
+#[allow(dead_code)]
+impl ScreenshotJob {
+    pub fn get_status(&self) -> JobStatus {
+        *self.status.lock().unwrap()
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.get_status(), JobStatus::Finished | JobStatus::Cancelled)
+    }
+}
+
+// Renders screenshots one after another on a background thread
+pub struct ScreenshotQueue {
+    sender: Option<mpsc::Sender<Arc<ScreenshotJob>>>, // None once shut down
+    worker: Option<JoinHandle<()>>,
+    jobs: Vec<Arc<ScreenshotJob>>,
+    next_id: u32,
+}
+
+#[allow(dead_code)]
+impl ScreenshotQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel::<Arc<ScreenshotJob>>();
+
+        let worker = thread::spawn(move || {
+            for job in receiver {
+                Self::run(&job);
+            }
+        });
+
+        Self { sender: Some(sender), worker: Some(worker), jobs: Vec::new(), next_id: 1 }
+    }
+
+    fn run(job: &ScreenshotJob) {
+        if job.cancellation.is_cancelled() {
+            *job.status.lock().unwrap() = JobStatus::Cancelled;
+            return;
+        }
+
+        println!("Starting screenshot {}", job.id);
+        super::take_screenshot(&job.camera, &job.render_config, &job.target, &mut |progress| {
+            *job.status.lock().unwrap() = JobStatus::Rendering(*progress);
+        }, &job.cancellation);
+
+        if job.cancellation.is_cancelled() { *job.status.lock().unwrap() = JobStatus::Cancelled; }
+        else { *job.status.lock().unwrap() = JobStatus::Finished; }
+    }
+
+    // Queues a screenshot from 'camera' as it is now, returning the job's id
+    // The job keeps 'render_config' alive until it's done, so the scene can't be edited while screenshots are pending
+    pub fn push(&mut self, camera: &Camera, render_config: &Arc<RenderConfig>) -> u32 {
+        let job = Arc::new(ScreenshotJob {
+            id: self.next_id,
+            camera: *camera,
+            render_config: render_config.clone(),
+            target: RenderTarget::new(render_config.screenshot_resolution),
+            cancellation: CancellationToken::new(),
+            status: Mutex::new(JobStatus::Queued),
+        });
+        self.next_id += 1;
+
+        self.jobs.push(job.clone());
+        match &self.sender {
+            Some(sender) => sender.send(job).unwrap(),
+            None => *job.status.lock().unwrap() = JobStatus::Cancelled,
+        }
+        self.next_id - 1
+    }
+
+    // Jobs that are queued or rendering, oldest first
+    pub fn get_pending(&mut self) -> &[Arc<ScreenshotJob>] {
+        self.jobs.retain(|job| !job.is_done());
+        &self.jobs
+    }
+
+    pub fn get_active(&mut self) -> Option<Arc<ScreenshotJob>> {
+        self.get_pending().first().cloned()
+    }
+
+    pub fn cancel_active(&mut self) {
+        if let Some(job) = self.get_active() {
+            println!("Cancelling screenshot {}...", job.id);
+            job.cancellation.cancel();
+        }
+    }
+
+    pub fn cancel_all(&mut self) {
+        for job in self.get_pending() {
+            println!("Cancelling screenshot {}...", job.id);
+            job.cancellation.cancel();
+        }
+    }
+
+    // Cancels every job and waits for the worker to stop, so nothing is left half written when the program exits
+    pub fn shutdown(&mut self) {
+        self.cancel_all();
+        self.sender = None;
+
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() { println!("Screenshot thread panicked!"); }
+        }
+    }
+}
+
+impl Drop for ScreenshotQueue {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -0,0 +1,427 @@
+use std::fs;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::time::Duration;
+
+use crate::colour::Colour;
+
+use crate::maths::{vectors::V3, quaternion::Quaternion, sampler::SamplerType};
+
+use super::{Camera, RenderConfig, Projection, FisheyeMapping};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[allow(dead_code)]
+impl Tile {
+    // Tiles covering the image, ordered in a spiral outwards from the centre so the interesting part finishes first
+    pub fn get_spiral(resolution: (u32, u32), tile_size: u32) -> Vec<Tile> {
+        let columns = resolution.0.div_ceil(tile_size);
+        let rows = resolution.1.div_ceil(tile_size);
+        let centre = ((columns as f64 - 1.0) / 2.0, (rows as f64 - 1.0) / 2.0);
+
+        let mut tiles = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = (column * tile_size, row * tile_size);
+                tiles.push(Tile { x, y, width: tile_size.min(resolution.0 - x), height: tile_size.min(resolution.1 - y) });
+            }
+        }
+
+        // Ring by ring, going round each ring clockwise
+        let get_key = |tile: &Tile| {
+            let dx = (tile.x / tile_size) as f64 - centre.0;
+            let dy = (tile.y / tile_size) as f64 - centre.1;
+            (dx.abs().max(dy.abs()), dy.atan2(dx))
+        };
+        tiles.sort_by(|a, b| get_key(a).partial_cmp(&get_key(b)).unwrap());
+
+        tiles
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct RenderProgress {
+    pub completed_tiles: usize,
+    pub total_tiles: usize,
+    pub elapsed: Duration,
+}
+
+#[allow(dead_code)]
+impl RenderProgress {
+    pub fn get_fraction(&self) -> f64 {
+        if self.total_tiles == 0 { return 1.0; }
+        self.completed_tiles as f64 / self.total_tiles as f64
+    }
+
+    // Estimated time remaining, assuming the remaining tiles take as long as the finished ones
+    pub fn get_eta(&self) -> Option<Duration> {
+        if self.completed_tiles == 0 { return None; }
+        let remaining = (self.total_tiles - self.completed_tiles) as f64 / self.completed_tiles as f64;
+        Some(self.elapsed.mul_f64(remaining))
+    }
+}
+
+// Shared flag for stopping a render early, cloned handles all refer to the same flag
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[allow(dead_code)]
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self { cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Running totals for one pixel - everything needed to carry on sampling it later
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct PixelState {
+    pub total_colour: Colour, // Filter weighted sum of samples
+    pub total_weight: f64,
+    pub samples: u32, // Samples taken, including those the filter gave no weight, so the next sample index
+    pub count: u32, // Samples contributing to the luminance statistics
+    pub mean: f64,
+    pub m2: f64,
+}
+
+impl Default for PixelState {
+    fn default() -> Self {
+        Self { total_colour: Colour::BLACK, total_weight: 0.0, samples: 0, count: 0, mean: 0.0, m2: 0.0 }
+    }
+}
+
+#[allow(dead_code)]
+impl PixelState {
+    pub fn add_sample(&mut self, colour: &Colour, weight: f64) {
+        self.total_colour = self.total_colour + (colour * weight);
+        self.total_weight += weight;
+        self.samples += 1;
+
+        // Running variance of the luminance (Welford)
+        self.count += 1;
+        let delta = colour.luminance() - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (colour.luminance() - self.mean);
+    }
+
+    pub fn get_standard_error(&self) -> Option<f64> {
+        if self.count < 2 { return None; }
+        Some((self.m2 / ((self.count - 1) * self.count) as f64).sqrt())
+    }
+
+    // Linear, before it is clamped for display
+    pub fn get_colour(&self) -> Colour {
+        if self.total_weight <= 0.0 { return Colour::BLACK; }
+        self.total_colour / self.total_weight
+    }
+}
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"RTCP";
+const CHECKPOINT_VERSION: u32 = 3;
+
+// Everything a checkpoint's samples depend on besides the scene, which has to match for a resumed render to carry on where it left off
+// The camera is stored whole, so a screenshot can be resumed from wherever the viewer's camera was when it was taken
+#[derive(Debug, Clone)]
+pub struct CheckpointHeader {
+    pub resolution: (u32, u32),
+    pub seed: u64,
+    pub sample_target: u32,
+    pub sampler: SamplerType,
+    pub filter: String,
+    pub camera: Camera,
+}
+
+impl CheckpointHeader {
+    pub fn new(camera: &Camera, render_config: &RenderConfig) -> Self {
+        Self {
+            resolution: render_config.screenshot_resolution,
+            seed: render_config.seed,
+            sample_target: render_config.get_pixel_samples(true),
+            sampler: render_config.sampler,
+            filter: format!("{:?}", render_config.filter),
+            camera: *camera,
+        }
+    }
+
+    // Just the header of the checkpoint at 'path'
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("Failed to read checkpoint '{}': {}", path, e))?;
+        Self::read(&mut CheckpointReader::new(&data, path)?)
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&self.resolution.0.to_le_bytes());
+        data.extend_from_slice(&self.resolution.1.to_le_bytes());
+        data.extend_from_slice(&self.seed.to_le_bytes());
+        data.extend_from_slice(&self.sample_target.to_le_bytes());
+        data.extend_from_slice(&(SAMPLER_TYPES.iter().position(|s| *s == self.sampler).unwrap() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.filter.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.filter.as_bytes());
+        Self::write_camera(&self.camera, data);
+    }
+
+    fn read(reader: &mut CheckpointReader) -> Result<Self, String> {
+        Ok(Self {
+            resolution: (reader.read_u32()?, reader.read_u32()?),
+            seed: reader.read_u64()?,
+            sample_target: reader.read_u32()?,
+            sampler: *SAMPLER_TYPES.get(reader.read_u32()? as usize).ok_or("Checkpoint has an unknown sampler")?,
+            filter: reader.read_string()?,
+            camera: Self::read_camera(reader)?,
+        })
+    }
+
+    fn write_camera(camera: &Camera, data: &mut Vec<u8>) {
+        let mut values = vec![camera.position.x, camera.position.y, camera.position.z];
+        values.extend([camera.orientation.w, camera.orientation.x, camera.orientation.y, camera.orientation.z]);
+        values.extend([camera.fov, camera.aperture_radius, camera.focus_distance, camera.aperture_rotation, camera.shutter.0, camera.shutter.1]);
+
+        // Projections as a kind followed by their two parameters, unused ones left at 0
+        let projection = match camera.projection {
+            Projection::Perspective => [0.0, 0.0, 0.0],
+            Projection::Orthographic(height) => [1.0, height, 0.0],
+            Projection::Fisheye(FisheyeMapping::Equidistant, fov) => [2.0, fov, 0.0],
+            Projection::Fisheye(FisheyeMapping::Equisolid, fov) => [2.0, fov, 1.0],
+            Projection::Equirectangular => [3.0, 0.0, 0.0],
+        };
+        values.extend(projection);
+
+        match camera.motion {
+            None => values.extend([0.0; 8]),
+            Some((position, orientation)) => values.extend([1.0, position.x, position.y, position.z, orientation.w, orientation.x, orientation.y, orientation.z]),
+        }
+
+        data.extend_from_slice(&camera.aperture_blades.to_le_bytes());
+        for value in values { data.extend_from_slice(&value.to_le_bytes()); }
+    }
+
+    fn read_camera(reader: &mut CheckpointReader) -> Result<Camera, String> {
+        let aperture_blades = reader.read_u32()?;
+        let mut values = Vec::with_capacity(24);
+        for _ in 0..24 { values.push(reader.read_f64()?); }
+        let v = &values;
+
+        let projection = match (v[13] as u32, v[15] != 0.0) {
+            (0, _) => Projection::Perspective,
+            (1, _) => Projection::Orthographic(v[14]),
+            (2, false) => Projection::Fisheye(FisheyeMapping::Equidistant, v[14]),
+            (2, true) => Projection::Fisheye(FisheyeMapping::Equisolid, v[14]),
+            (3, _) => Projection::Equirectangular,
+            _ => return Err("Checkpoint has an unknown projection".to_string()),
+        };
+        let motion = if v[16] == 0.0 { None } else { Some((V3::new(v[17], v[18], v[19]), Quaternion::new(v[20], v[21], v[22], v[23]))) };
+
+        Ok(Camera {
+            position: V3::new(v[0], v[1], v[2]),
+            orientation: Quaternion::new(v[3], v[4], v[5], v[6]),
+            fov: v[7], aperture_radius: v[8], focus_distance: v[9], aperture_blades, aperture_rotation: v[10],
+            projection,
+            shutter: (v[11], v[12]),
+            motion,
+        })
+    }
+
+    // Describes the first difference from 'render' that would stop it carrying on from this checkpoint
+    // The sample target may go up, as sample streams don't depend on it - except the stratified sampler's, whose strata do
+    pub fn check(&self, render: &Self) -> Result<(), String> {
+        if self.resolution != render.resolution {
+            return Err(format!("Checkpoint is {}x{} but the render is {}x{}", self.resolution.0, self.resolution.1, render.resolution.0, render.resolution.1));
+        }
+        if self.seed != render.seed { return Err(format!("Checkpoint used seed {} but the render uses seed {}", self.seed, render.seed)); }
+        if self.sampler != render.sampler { return Err(format!("Checkpoint used the {:?} sampler but the render uses {:?}", self.sampler, render.sampler)); }
+        if self.filter != render.filter { return Err(format!("Checkpoint used filter {} but the render uses {}", self.filter, render.filter)); }
+
+        if render.sample_target < self.sample_target {
+            return Err(format!("Checkpoint renders {} samples per pixel, the render can't take fewer ({})", self.sample_target, render.sample_target));
+        }
+        if self.sampler == SamplerType::Stratified && render.sample_target != self.sample_target {
+            return Err(format!("Stratified checkpoints can only be resumed at the same {} samples per pixel", self.sample_target));
+        }
+
+        let (mut checkpoint_camera, mut render_camera) = (Vec::new(), Vec::new());
+        Self::write_camera(&self.camera, &mut checkpoint_camera);
+        Self::write_camera(&render.camera, &mut render_camera);
+        if checkpoint_camera != render_camera { return Err(format!("Checkpoint used camera {:?} but the render uses {:?}", self.camera, render.camera)); }
+        Ok(())
+    }
+}
+
+// In the order they're stored in checkpoints
+const SAMPLER_TYPES: [SamplerType; 5] = [SamplerType::Independent, SamplerType::Stratified, SamplerType::Halton, SamplerType::Sobol, SamplerType::BlueNoise];
+
+// Image being filled in tile by tile, readable while the render is in progress
+#[allow(dead_code)]
+pub struct RenderTarget {
+    pub resolution: (u32, u32),
+    pixels: Mutex<Vec<PixelState>>,
+}
+
+#[allow(dead_code)]
+impl RenderTarget {
+    pub fn new(resolution: (u32, u32)) -> Self {
+        Self { resolution, pixels: Mutex::new(vec![PixelState::default(); (resolution.0 * resolution.1) as usize]) }
+    }
+
+    // The tile's pixels row by row
+    pub fn read_tile(&self, tile: &Tile) -> Vec<PixelState> {
+        let pixels = self.pixels.lock().unwrap();
+
+        let mut result = Vec::with_capacity((tile.width * tile.height) as usize);
+        for y in tile.y..(tile.y + tile.height) {
+            let start = (y * self.resolution.0 + tile.x) as usize;
+            result.extend_from_slice(&pixels[start..start + tile.width as usize]);
+        }
+        result
+    }
+
+    pub fn write_tile(&self, tile: &Tile, tile_pixels: &[PixelState]) {
+        let mut pixels = self.pixels.lock().unwrap();
+
+        for (row, y) in (tile.y..(tile.y + tile.height)).enumerate() {
+            let start = (y * self.resolution.0 + tile.x) as usize;
+            let row_start = row * tile.width as usize;
+            pixels[start..start + tile.width as usize].copy_from_slice(&tile_pixels[row_start..row_start + tile.width as usize]);
+        }
+    }
+
+    pub fn get_colours(&self) -> Vec<Colour> {
+        self.pixels.lock().unwrap().iter().map(|p| p.get_colour()).collect()
+    }
+
+    pub fn get_pixel_data(&self) -> Vec<u8> {
+        super::get_pixel_data(&self.get_colours())
+    }
+
+    pub fn get_sample_counts(&self) -> Vec<u32> {
+        self.pixels.lock().unwrap().iter().map(|p| p.samples).collect()
+    }
+
+    // Sample streams only depend on the seed, pixel and sample index, so the header and the per pixel
+    // sample counts are all the sampler state needed to carry on exactly where the render left off
+    pub fn save_checkpoint(&self, path: &str, header: &CheckpointHeader) -> Result<(), String> {
+        if header.resolution != self.resolution { return Err("Checkpoint header doesn't match the render target".to_string()); }
+        let pixels = self.pixels.lock().unwrap().clone();
+
+        let mut data = Vec::with_capacity(256 + pixels.len() * 56);
+        data.extend_from_slice(CHECKPOINT_MAGIC);
+        data.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        header.write(&mut data);
+
+        for pixel in pixels {
+            let (r, g, b) = pixel.total_colour.as_f64();
+            for value in [r, g, b, pixel.total_weight, pixel.mean, pixel.m2] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            data.extend_from_slice(&pixel.samples.to_le_bytes());
+            data.extend_from_slice(&pixel.count.to_le_bytes());
+        }
+
+        // Write then rename so a crash mid-save never leaves a truncated checkpoint behind
+        let temporary_path = format!("{}.tmp", path);
+        fs::write(&temporary_path, data).map_err(|e| format!("Failed to write checkpoint '{}': {}", temporary_path, e))?;
+        fs::rename(&temporary_path, path).map_err(|e| format!("Failed to move checkpoint to '{}': {}", path, e))
+    }
+
+    // Replaces this target's pixels with a checkpoint's, which must have been saved with the same 'header'
+    pub fn load_checkpoint(&self, path: &str, header: &CheckpointHeader) -> Result<(), String> {
+        let data = fs::read(path).map_err(|e| format!("Failed to read checkpoint '{}': {}", path, e))?;
+        let mut reader = CheckpointReader::new(&data, path)?;
+
+        if header.resolution != self.resolution { return Err("Checkpoint header doesn't match the render target".to_string()); }
+        CheckpointHeader::read(&mut reader)?.check(header)?;
+        let resolution = self.resolution;
+
+        let mut pixels = Vec::with_capacity((resolution.0 * resolution.1) as usize);
+        for _ in 0..(resolution.0 * resolution.1) {
+            let (r, g, b) = (reader.read_f64()?, reader.read_f64()?, reader.read_f64()?);
+            let (total_weight, mean, m2) = (reader.read_f64()?, reader.read_f64()?, reader.read_f64()?);
+            pixels.push(PixelState {
+                total_colour: Colour::from_f64(r, g, b), total_weight, mean, m2,
+                samples: reader.read_u32()?, count: reader.read_u32()?,
+            });
+        }
+
+        *self.pixels.lock().unwrap() = pixels;
+        Ok(())
+    }
+}
+
+struct CheckpointReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> CheckpointReader<'a> {
+    // Starts reading after the magic and version, which must be this build's
+    fn new(data: &'a [u8], path: &str) -> Result<Self, String> {
+        let mut reader = Self { data, position: 0 };
+        if reader.read(4)? != CHECKPOINT_MAGIC { return Err(format!("'{}' is not a checkpoint", path)); }
+        let version = reader.read_u32()?;
+        if version != CHECKPOINT_VERSION { return Err(format!("Unsupported checkpoint version {}", version)); }
+        Ok(reader)
+    }
+
+    fn read(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if self.position + length > self.data.len() { return Err("Checkpoint is truncated".to_string()); }
+        let result = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(result)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.read(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.read(8)?.try_into().unwrap()))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.read(8)?.try_into().unwrap()))
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let length = self.read_u32()? as usize;
+        String::from_utf8(self.read(length)?.to_vec()).map_err(|_| "Checkpoint is corrupt".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tile;
+
+    #[test]
+    fn spiral_tiles_cover_every_pixel_once_starting_from_the_centre() {
+        let resolution = (90, 70);
+        let tiles = Tile::get_spiral(resolution, 32);
+        let mut covered = vec![0; (resolution.0 * resolution.1) as usize];
+
+        for tile in &tiles {
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width { covered[(y * resolution.0 + x) as usize] += 1; }
+            }
+        }
+
+        assert_eq!(tiles.len(), 3 * 3);
+        assert!(covered.iter().all(|c| *c == 1));
+        assert!(tiles[0].x == 32 && tiles[0].y == 32, "{:?}", tiles[0]);
+    }
+}
@@ -0,0 +1,172 @@
+pub mod scene_file;
+
+use std::sync::Arc;
+
+use crate::maths::transform::Transform;
+use crate::objects::{Object, Instance};
+use crate::lights::{Light, TransformedLight};
+
+// What the renderer takes, see RenderConfig::scene_objects and scene_lights
+pub type SceneObjects = Vec<Box<dyn Object + Sync + Send>>;
+pub type SceneLights = Vec<Box<dyn Light + Sync + Send>>;
+
+#[allow(dead_code)]
+pub enum NodeContent {
+    Group, // Only holds children
+    Object(Arc<dyn Object + Sync + Send>),
+    Light(Arc<dyn Light + Sync + Send>),
+}
+
+// Node in a tree of objects and lights, each placed relative to its parent
+#[allow(dead_code)]
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Transform, // Local space to the parent's space
+    pub content: NodeContent,
+    pub children: Vec<SceneNode>,
+}
+
+#[allow(dead_code)]
+impl SceneNode {
+    pub fn new(name: &str, content: NodeContent) -> Self {
+        Self { name: name.to_string(), transform: Transform::IDENTITY, content, children: Vec::new() }
+    }
+
+    pub fn group(name: &str) -> Self {
+        Self::new(name, NodeContent::Group)
+    }
+
+    pub fn object(name: &str, object: Arc<dyn Object + Sync + Send>) -> Self {
+        Self::new(name, NodeContent::Object(object))
+    }
+
+    pub fn light(name: &str, light: Arc<dyn Light + Sync + Send>) -> Self {
+        Self::new(name, NodeContent::Light(light))
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_child(mut self, child: SceneNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn add_child(&mut self, child: SceneNode) {
+        self.children.push(child);
+    }
+
+    // World space objects and lights for the renderer, every node placed by the transforms of all its ancestors
+    pub fn flatten(&self) -> (SceneObjects, SceneLights) {
+        let (mut objects, mut lights) = (Vec::new(), Vec::new());
+        self.flatten_into(&Transform::IDENTITY, &mut objects, &mut lights);
+        (objects, lights)
+    }
+
+    fn flatten_into(&self, parent: &Transform, objects: &mut SceneObjects, lights: &mut SceneLights) {
+        let transform = *parent * self.transform;
+
+        match &self.content {
+            NodeContent::Group => {},
+            // Nodes that aren't moved skip the wrapper, and its transforms on every ray
+            NodeContent::Object(object) if transform.is_identity() => objects.push(Box::new(object.clone())),
+            NodeContent::Light(light) if transform.is_identity() => lights.push(Box::new(light.clone())),
+            NodeContent::Object(object) => objects.push(Box::new(Instance::new(object.clone(), transform))),
+            NodeContent::Light(light) => match TransformedLight::new(light.clone(), transform) {
+                Some(light) => lights.push(Box::new(light)),
+                None => println!("Light '{}' is scaled non-uniformly, leaving it out", self.name),
+            },
+        }
+
+        for child in &self.children {
+            child.flatten_into(&transform, objects, lights);
+        }
+    }
+
+    // Including this node
+    pub fn get_node_count(&self) -> usize {
+        1 + self.children.iter().map(|c| c.get_node_count()).sum::<usize>()
+    }
+
+    // Nodes are numbered depth first, this node being 0
+    pub fn get_node_mut(&mut self, index: usize) -> Option<&mut SceneNode> {
+        if index == 0 { return Some(self); }
+
+        let mut index = index - 1;
+        for child in self.children.iter_mut() {
+            let count = child.get_node_count();
+            if index < count { return child.get_node_mut(index); }
+            index -= count;
+        }
+        None
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        if self.name == name { return Some(self); }
+        self.children.iter_mut().find_map(|c| c.find_mut(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::f64::consts::PI;
+
+    use crate::colour::Colour;
+    use crate::lights::{Light, PointLight};
+    use crate::maths::{vectors::V3, lines::Line, quaternion::Quaternion, transform::Transform, sampler::IndependentSampler, Intersection};
+    use crate::objects::{Object, Sphere, SurfaceType};
+
+    use super::{SceneNode, SceneObjects};
+
+    // Distance along 'ray' to the first of 'objects' it hits
+    fn get_distance(objects: &SceneObjects, ray: &Line) -> Option<f64> {
+        objects.iter().filter_map(|o| Intersection::closest(&o.get_intersections(ray)).map(|i| i.sized_line.length())).reduce(f64::min)
+    }
+
+    fn get_table() -> SceneNode {
+        let sphere: Arc<dyn Object + Sync + Send> = Arc::new(Sphere::new(V3::ZERO, 0.5, Colour::WHITE, SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false)));
+        let light: Arc<dyn Light + Sync + Send> = Arc::new(PointLight::new(&V3::ZERO, &Colour::WHITE, 1.0));
+
+        // Turning the table a quarter turn swings its leg from +x round to -z
+        SceneNode::group("Scene").with_child(SceneNode::group("Table")
+            .with_transform(Transform::translation(&V3::new(0.0, 0.0, 10.0)) * Transform::rotation(&Quaternion::from_axis_angle(&V3::UP, PI / 2.0)))
+            .with_child(SceneNode::object("Leg", sphere).with_transform(Transform::translation(&V3::new(2.0, 0.0, 0.0))))
+            .with_child(SceneNode::light("Lamp", light).with_transform(Transform::translation(&V3::new(0.0, 3.0, 0.0)))))
+    }
+
+    #[test]
+    fn flattening_applies_every_ancestor_transform() {
+        let (objects, lights) = get_table().flatten();
+        assert_eq!((objects.len(), lights.len()), (1, 1));
+
+        let ray = Line::new(&V3::ZERO, &V3::RIGHT);
+        assert!((get_distance(&objects, &ray).unwrap() - 7.5).abs() < 1e-9);
+
+        let sample = lights[0].sample(&V3::new(0.0, 0.0, 10.0), &mut IndependentSampler::new(0));
+        assert!((sample.point - V3::new(0.0, 3.0, 10.0)).magnitude() < 1e-9);
+        assert!((sample.distance - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn editing_a_group_moves_everything_in_it() {
+        let mut scene = get_table();
+        scene.find_mut("Table").unwrap().transform = Transform::translation(&V3::new(0.0, 0.0, 20.0));
+        let (objects, _) = scene.flatten();
+
+        assert!(get_distance(&objects, &Line::new(&V3::ZERO, &V3::RIGHT)).is_none());
+        assert!(get_distance(&objects, &Line::new(&V3::new(-5.0, 0.0, 20.0), &V3::FORWARD)).is_some());
+    }
+
+    #[test]
+    fn nodes_are_numbered_depth_first() {
+        let mut scene = get_table();
+        assert_eq!(scene.get_node_count(), 4);
+
+        let names: Vec<String> = (0..4).map(|i| scene.get_node_mut(i).unwrap().name.clone()).collect();
+        assert_eq!(names, ["Scene", "Table", "Leg", "Lamp"]);
+        assert!(scene.get_node_mut(4).is_none());
+    }
+}
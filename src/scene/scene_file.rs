@@ -0,0 +1,287 @@
+use std::{fs, sync::Arc};
+
+use crate::colour::{Colour, colour_getters::{ColourGetter, SolidColour, Texture}};
+use crate::lights::{Light, PointLight, DirectionalLight, RectLight, DiscLight, SphereLight, SpotLight};
+use crate::maths::{vectors::V3, quaternion::Quaternion, transform::Transform};
+use crate::objects::{Object, Sphere, Plane, SurfaceType};
+
+use super::SceneNode;
+
+// Text scene description, one node per line:
+//
+//     # Comment
+//     group Table position=0,0,10 rotation=0,1,0,90
+//         sphere Leg position=2,0,0 radius=0.5 colour=1,1,1
+//         point_light Lamp position=0,3,0 colour=1,1,0.8 intensity=200
+//     end
+//
+// Every node takes 'position=x,y,z', 'rotation=axis_x,axis_y,axis_z,degrees' and 'scale=s' or 'scale=x,y,z'
+// relative to its parent. Groups hold the nodes up to their 'end'. Names can't contain spaces.
+//
+//     sphere             radius [colour] [surface] [emission]
+//     plane              vector_one vector_two [limits] [colour | texture] [surface]
+//     point_light        [colour] intensity
+//     directional_light  direction [colour] intensity
+//     rect_light         vector_one vector_two [colour] intensity
+//     disc_light         normal radius [colour] intensity
+//     sphere_light       radius [colour] intensity
+//     spot_light         direction inner_angle outer_angle [colour] intensity [gobo]
+//
+// 'surface=diffuseness,reflectiveness,transparency,refractive_index' and 'emission=r,g,b,strength'
+// Spot light angles are in degrees and 'gobo' is the path of a texture projected through the cone
+pub fn load(path: &str) -> Result<SceneNode, String> {
+    println!("Loading scene [{}]", path);
+
+    let text = fs::read_to_string(path).map_err(|e| format!("Loading scene failed [{}]: {}", path, e))?;
+    parse(&text, path)
+}
+
+// 'source' names the text in error messages
+pub fn parse(text: &str, source: &str) -> Result<SceneNode, String> {
+    // Groups still waiting for their 'end', the scene itself at the bottom
+    let mut stack = vec![SceneNode::group("Scene")];
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() { continue; }
+
+        let error = |message: String| format!("{}:{}: {}", source, i + 1, message);
+
+        if line == "end" {
+            if stack.len() == 1 { return Err(error("'end' without a group".to_string())); }
+            let group = stack.pop().unwrap();
+            stack.last_mut().unwrap().add_child(group);
+            continue;
+        }
+
+        let node = NodeLine::parse(line).and_then(|n| n.create()).map_err(error)?;
+        match node {
+            (node, true) => stack.push(node),
+            (node, false) => stack.last_mut().unwrap().add_child(node),
+        }
+    }
+
+    if stack.len() > 1 { return Err(format!("{}: group '{}' has no 'end'", source, stack.last().unwrap().name)); }
+    Ok(stack.pop().unwrap())
+}
+
+struct NodeLine<'a> {
+    kind: &'a str,
+    name: &'a str,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> NodeLine<'a> {
+    fn parse(line: &'a str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let kind = words.next().unwrap();
+        let name = words.next().ok_or(format!("{} has no name", kind))?;
+
+        let mut options = Vec::new();
+        for word in words {
+            let (key, value) = word.split_once('=').ok_or(format!("expected 'key=value', found '{}'", word))?;
+            options.push((key, value));
+        }
+
+        Ok(Self { kind, name, options })
+    }
+
+    // Returns the node and whether it's a group whose children follow
+    fn create(&self) -> Result<(SceneNode, bool), String> {
+        let (node, allowed, is_group): (SceneNode, &[&str], bool) = match self.kind {
+            "group" => (SceneNode::group(self.name), &[], true),
+            "sphere" => (SceneNode::object(self.name, self.create_sphere()?), &["radius", "colour", "surface", "emission"], false),
+            "plane" => (SceneNode::object(self.name, self.create_plane()?), &["vector_one", "vector_two", "limits", "colour", "texture", "surface"], false),
+            "point_light" => (SceneNode::light(self.name, self.create_point_light()?), &["colour", "intensity"], false),
+            "directional_light" => (SceneNode::light(self.name, self.create_directional_light()?), &["direction", "colour", "intensity"], false),
+            "rect_light" => (SceneNode::light(self.name, self.create_rect_light()?), &["vector_one", "vector_two", "colour", "intensity"], false),
+            "disc_light" => (SceneNode::light(self.name, self.create_disc_light()?), &["normal", "radius", "colour", "intensity"], false),
+            "sphere_light" => (SceneNode::light(self.name, self.create_sphere_light()?), &["radius", "colour", "intensity"], false),
+            "spot_light" => (SceneNode::light(self.name, self.create_spot_light()?), &["direction", "inner_angle", "outer_angle", "colour", "intensity", "gobo"], false),
+            _ => return Err(format!("unknown node type '{}'", self.kind)),
+        };
+
+        for (key, _) in &self.options {
+            if !allowed.contains(key) && !["position", "rotation", "scale"].contains(key) {
+                return Err(format!("{} doesn't take '{}'", self.kind, key));
+            }
+        }
+
+        let transform = self.get_transform()?;
+        if self.kind.ends_with("_light") && transform.get_uniform_scale().is_none() { return Err(format!("{} can only be scaled uniformly", self.kind)); }
+
+        Ok((node.with_transform(transform), is_group))
+    }
+
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.options.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+    }
+
+    fn get_numbers(&self, key: &str, count: &[usize]) -> Result<Option<Vec<f64>>, String> {
+        let Some(value) = self.get(key) else { return Ok(None); };
+
+        let numbers = value.split(',').map(|n| n.parse::<f64>()).collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| format!("'{}' should be numbers, found '{}'", key, value))?;
+        if !count.contains(&numbers.len()) { return Err(format!("'{}' should have {:?} numbers, found {}", key, count, numbers.len())); }
+
+        Ok(Some(numbers))
+    }
+
+    fn get_f64(&self, key: &str) -> Result<Option<f64>, String> {
+        Ok(self.get_numbers(key, &[1])?.map(|n| n[0]))
+    }
+
+    fn get_v3(&self, key: &str) -> Result<Option<V3>, String> {
+        Ok(self.get_numbers(key, &[3])?.map(|n| V3::new(n[0], n[1], n[2])))
+    }
+
+    fn get_colour(&self) -> Result<Colour, String> {
+        Ok(self.get_numbers("colour", &[3])?.map_or(Colour::WHITE, |n| Colour::from_f64(n[0], n[1], n[2])))
+    }
+
+    fn require<T>(&self, key: &str, value: Result<Option<T>, String>) -> Result<T, String> {
+        value?.ok_or(format!("{} needs '{}'", self.kind, key))
+    }
+
+    fn get_transform(&self) -> Result<Transform, String> {
+        let position = self.get_v3("position")?.unwrap_or(V3::ZERO);
+        let rotation = match self.get_numbers("rotation", &[4])? {
+            Some(n) => Quaternion::from_axis_angle(&V3::new(n[0], n[1], n[2]), n[3].to_radians()),
+            None => Quaternion::IDENTITY,
+        };
+        let scale = match self.get_numbers("scale", &[1, 3])? {
+            Some(n) if n.len() == 1 => V3::ONE * n[0],
+            Some(n) => V3::new(n[0], n[1], n[2]),
+            None => V3::ONE,
+        };
+
+        Ok(Transform::from_parts(&position, &rotation, &scale))
+    }
+
+    fn get_surface_type(&self) -> Result<SurfaceType, String> {
+        if let Some(n) = self.get_numbers("emission", &[4])? {
+            return Ok(SurfaceType::new_emissive(&Colour::from_f64(n[0], n[1], n[2]), n[3]));
+        }
+
+        Ok(match self.get_numbers("surface", &[4])? {
+            Some(n) => SurfaceType::new(n[0], n[1], n[2], n[3], true, false),
+            None => SurfaceType::new(1.0, 0.0, 0.0, 1.0, true, false),
+        })
+    }
+
+    fn create_sphere(&self) -> Result<Arc<dyn Object + Sync + Send>, String> {
+        let radius = self.require("radius", self.get_f64("radius"))?;
+        Ok(Arc::new(Sphere::new(V3::ZERO, radius, self.get_colour()?, self.get_surface_type()?)))
+    }
+
+    fn create_plane(&self) -> Result<Arc<dyn Object + Sync + Send>, String> {
+        let vector_one = self.require("vector_one", self.get_v3("vector_one"))?;
+        let vector_two = self.require("vector_two", self.get_v3("vector_two"))?;
+        let limits = self.get_numbers("limits", &[4])?.map(|n| (n[0], n[1], n[2], n[3]));
+
+        let colour_getter: Box<dyn ColourGetter + Sync + Send> = match self.get("texture") {
+            Some(path) => Box::new(Texture::new(path)?),
+            None => Box::new(SolidColour { colour: self.get_colour()? }),
+        };
+
+        Ok(Arc::new(Plane::new(&V3::ZERO, &vector_one, &vector_two, limits, colour_getter, self.get_surface_type()?)))
+    }
+
+    fn create_point_light(&self) -> Result<Arc<dyn Light + Sync + Send>, String> {
+        let intensity = self.require("intensity", self.get_f64("intensity"))?;
+        Ok(Arc::new(PointLight::new(&V3::ZERO, &self.get_colour()?, intensity)))
+    }
+
+    fn create_directional_light(&self) -> Result<Arc<dyn Light + Sync + Send>, String> {
+        let direction = self.require("direction", self.get_v3("direction"))?;
+        let intensity = self.require("intensity", self.get_f64("intensity"))?;
+        Ok(Arc::new(DirectionalLight::new(&direction, &self.get_colour()?, intensity)))
+    }
+
+    fn create_rect_light(&self) -> Result<Arc<dyn Light + Sync + Send>, String> {
+        let vector_one = self.require("vector_one", self.get_v3("vector_one"))?;
+        let vector_two = self.require("vector_two", self.get_v3("vector_two"))?;
+        let intensity = self.require("intensity", self.get_f64("intensity"))?;
+        Ok(Arc::new(RectLight::new(&V3::ZERO, &vector_one, &vector_two, &self.get_colour()?, intensity)))
+    }
+
+    fn create_disc_light(&self) -> Result<Arc<dyn Light + Sync + Send>, String> {
+        let normal = self.require("normal", self.get_v3("normal"))?;
+        let radius = self.require("radius", self.get_f64("radius"))?;
+        let intensity = self.require("intensity", self.get_f64("intensity"))?;
+        Ok(Arc::new(DiscLight::new(&V3::ZERO, &normal, radius, &self.get_colour()?, intensity)))
+    }
+
+    fn create_sphere_light(&self) -> Result<Arc<dyn Light + Sync + Send>, String> {
+        let radius = self.require("radius", self.get_f64("radius"))?;
+        let intensity = self.require("intensity", self.get_f64("intensity"))?;
+        Ok(Arc::new(SphereLight::new(&V3::ZERO, radius, &self.get_colour()?, intensity)))
+    }
+
+    fn create_spot_light(&self) -> Result<Arc<dyn Light + Sync + Send>, String> {
+        let direction = self.require("direction", self.get_v3("direction"))?;
+        let inner_angle = self.require("inner_angle", self.get_f64("inner_angle"))?;
+        let outer_angle = self.require("outer_angle", self.get_f64("outer_angle"))?;
+        let intensity = self.require("intensity", self.get_f64("intensity"))?;
+
+        let light = SpotLight::new(&V3::ZERO, &direction, inner_angle.to_radians(), outer_angle.to_radians(), &self.get_colour()?, intensity);
+        Ok(Arc::new(match self.get("gobo") {
+            Some(path) => light.with_gobo(Box::new(Texture::new(path)?)),
+            None => light,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maths::{lines::Line, vectors::V3, Intersection, sampler::IndependentSampler};
+
+    use super::parse;
+
+    #[test]
+    fn groups_place_their_children() {
+        let scene = parse("
+            # A table turned a quarter turn
+            group Table position=0,0,10 rotation=0,1,0,90
+                sphere Leg position=2,0,0 radius=0.5 colour=1,0,0 surface=1,0,0,1
+                point_light Lamp position=0,3,0 intensity=100
+            end
+            plane Floor vector_one=1,0,0 vector_two=0,0,1
+        ", "test").unwrap();
+
+        assert_eq!(scene.get_node_count(), 5);
+        let (objects, lights) = scene.flatten();
+        assert_eq!((objects.len(), lights.len()), (2, 1));
+
+        // The leg swings from +x round to -z
+        let hits = objects[0].get_intersections(&Line::new(&V3::ZERO, &V3::RIGHT));
+        assert!((Intersection::closest(&hits).unwrap().sized_line.scale - 7.5).abs() < 1e-9);
+        assert!((lights[0].get_direct_ray(&V3::new(0.0, 0.0, 10.0)).line.point - V3::new(0.0, 3.0, 10.0)).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn spot_lights_point_where_their_node_turns_them() {
+        let scene = parse("spot_light Lamp position=0,4,0 rotation=0,0,1,-90 direction=1,0,0 inner_angle=10 outer_angle=20 intensity=100", "test").unwrap();
+        let (_, lights) = scene.flatten();
+
+        // Turned from +x to straight down, so only points under the lamp are lit
+        let mut rng = IndependentSampler::new(0);
+        let below = lights[0].sample(&V3::ZERO, &mut rng);
+        let beside = lights[0].sample(&V3::new(4.0, 0.0, 0.0), &mut rng);
+        assert!(lights[0].get_sample_intensity(&below) > 0.0);
+        assert_eq!(lights[0].get_sample_intensity(&beside), 0.0);
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        let error = |text: &str| parse(text, "test").err().unwrap();
+
+        assert!(error("sphere Ball").starts_with("test:1: sphere needs 'radius'"));
+        assert!(error("\nsphere Ball radius=1 size=2").starts_with("test:2:"));
+        assert!(error("sphere Ball radius=one").contains("'radius'"));
+        assert!(error("cube Box").contains("unknown node type"));
+        assert!(error("end").contains("without a group"));
+        assert!(error("group Table").contains("no 'end'"));
+        assert!(error("point_light Lamp intensity=1 scale=1,2,1").contains("scaled uniformly"));
+    }
+}